@@ -14,7 +14,8 @@ def load_file(file_path):
 
     points = np.array(data["points"])
     lines = data["lines"]
-    return points, lines
+    line_ids = data.get("line_ids", list(range(len(lines))))
+    return points, lines, line_ids
 
 
 def plot_points(points):
@@ -31,13 +32,13 @@ def get_line_coords(point_x, point_y, line):
     y = point_y[line]
     return x, y
 
-def plot_lines(points, lines):
+def plot_lines(points, lines, line_ids):
     point_x = points[:, 0]
     point_y = points[:, 1]
-    color_iter = iter(colors.TABLEAU_COLORS.values())
+    palette = list(colors.TABLEAU_COLORS.values())
     mapped = map(lambda x: get_line_coords(point_x, point_y, x), lines)
-    for x, y in mapped:
-        color = next(color_iter)
+    for (x, y), line_id in zip(mapped, line_ids):
+        color = palette[line_id % len(palette)]
         plot_line(x, y, color)
 
 def get_input_file():
@@ -54,9 +55,9 @@ def get_input_file():
 
 def main():
     input_file = get_input_file()
-    points, lines = load_file(input_file)
+    points, lines, line_ids = load_file(input_file)
     plot_points(points)
-    plot_lines(points, lines)
+    plot_lines(points, lines, line_ids)
     plt.axis("off")
     plt.tight_layout()
     plt.show()
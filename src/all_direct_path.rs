@@ -1,8 +1,11 @@
 use super::build_graph;
-use simplegraph::{path_cost, GetGraphType, Graph, GraphVisitor};
+use simplegraph::{path_cost, GetGraphType, Graph, GraphType, GraphVisitor};
+use std::collections::BTreeMap;
 
 pub fn all_direct_path(mut net: build_graph::Network) -> build_graph::Network {
-    net.graph = build_all_direct_path_graph(net.graph, &net.lines);
+    let mut graph = build_all_direct_path_graph(net.graph, &net.lines);
+    add_walking_transfers(&mut graph, &net.transfers);
+    net.graph = graph;
     net
 }
 
@@ -11,25 +14,58 @@ fn build_all_direct_path_graph(
     lines: &build_graph::Lines,
 ) -> build_graph::NetGraph {
     let mut output = new_graph(&input);
+    let mut arcs = BTreeMap::new();
     for line in lines {
-        add_line_arcs(&input, &mut output, line);
+        add_line_arcs(&input, &mut arcs, line);
+    }
+    for ((i, j), w) in arcs {
+        output.add_new_arc(i, j, w);
     }
     output
 }
 
+/// Service patterns can connect the same pair of stations
+/// (e.g. branches sharing the trunk or express patterns):
+/// keep a single arc with the lowest cost.
 fn add_line_arcs(
     input: &build_graph::NetGraph,
-    output: &mut build_graph::NetGraph,
+    arcs: &mut BTreeMap<(usize, usize), f64>,
     line: &[usize],
 ) {
+    let gtype = input.graph_type();
     for (i, j, w) in path_cost::AllSubPathCost::new(input, line) {
-        output.add_new_arc(i, j, w)
+        // a line crossing itself visits the same station twice
+        if i == j {
+            continue;
+        }
+        let cost = arcs.entry(arc_key(gtype, i, j)).or_insert(w);
+        *cost = cost.min(w);
+    }
+}
+
+fn arc_key(gtype: GraphType, i: usize, j: usize) -> (usize, usize) {
+    match gtype {
+        GraphType::Direct => (i, j),
+        GraphType::Undirect => (i.min(j), i.max(j)),
     }
 }
 
+fn add_walking_transfers(output: &mut build_graph::NetGraph, transfers: &[build_graph::Transfer]) {
+    for transfer in transfers {
+        if transfer.kind == build_graph::TransferKind::Walking {
+            let (i, j) = transfer.stations;
+            output.add_new_arc(i, j, transfer.distance);
+        }
+    }
+}
+
+/// An empty graph with the same nodes, station wait times included.
 fn new_graph(g: &build_graph::NetGraph) -> build_graph::NetGraph {
     let nodes = g.node_count();
-    build_graph::NetGraph::new(nodes, g.graph_type())
+    let mut output = build_graph::NetGraph::new(nodes, g.graph_type());
+    let weights: Vec<f64> = g.node_iterator().map(|(_, w)| w).collect();
+    output.update_all_nodes_weight(|i, _| weights[i]);
+    output
 }
 
 #[cfg(test)]
@@ -68,6 +104,25 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_all_direct_shared_patterns() {
+        let mut network = init_network(true);
+        network.lines.push(vec![0, 1, 2, 5, 6]);
+        network.line_ids.push(0);
+        let network = all_direct_path(network);
+        // 20 arcs from the two lines plus (0, 5), (0, 6), (1, 5), (1, 6)
+        assert_eq!((&network.graph).arc_count(), 2 * 24);
+    }
+
+    #[test]
+    fn test_all_direct_keeps_wait_times() {
+        let mut network = init_network(true);
+        network.graph.update_all_nodes_weight(|i, _| i as f64);
+        let network = all_direct_path(network);
+        let weights: Vec<f64> = network.graph.node_iterator().map(|(_, w)| w).collect();
+        assert_eq!(weights, (0..9).map(|i| i as f64).collect::<Vec<_>>());
+    }
+
     fn init_network(undirect: bool) -> build_graph::Network {
         let mut graph = if undirect {
             build_graph::NetGraph::new_undirect(9)
@@ -79,11 +134,7 @@ mod test {
         }
         let lines = vec![vec![0, 1, 2, 3, 4], vec![6, 5, 2, 7, 8]];
 
-        build_graph::Network {
-            lines,
-            graph,
-            points: vec![],
-        }
+        build_graph::Network::new(vec![], graph, lines, vec![0, 1])
     }
 
     fn get_base_case_arcs() -> Vec<(usize, usize, f64)> {
@@ -0,0 +1,440 @@
+use crate::build_graph::{ArcServices, LineFrequency, Network};
+use crate::service_graph::ServiceGraph;
+use crate::shortest_path;
+use crate::MResult;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::Read;
+
+const DEFAULT_BPR_ALPHA: f64 = 0.15;
+const DEFAULT_BPR_BETA: f64 = 4.;
+
+fn get_default_bpr_alpha() -> f64 {
+    DEFAULT_BPR_ALPHA
+}
+
+fn get_default_bpr_beta() -> f64 {
+    DEFAULT_BPR_BETA
+}
+
+#[derive(Deserialize)]
+pub struct AssignmentConfig {
+    /// CSV file with a row of trips for each origin,
+    /// used in place of the generated demand.
+    pub demand_file: Option<String>,
+    #[serde(default)]
+    pub method: AssignmentMethod,
+    pub capacity: Option<CapacityConfig>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AssignmentMethod {
+    /// Every trip takes the shortest path on the free flow times.
+    #[default]
+    AllOrNothing,
+    /// Method of successive averages: arc times grow with the
+    /// load following the BPR function t0 (1 + alpha (v / c) ^ beta).
+    Msa {
+        iterations: usize,
+        #[serde(default = "get_default_bpr_alpha")]
+        alpha: f64,
+        #[serde(default = "get_default_bpr_beta")]
+        beta: f64,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub struct LineCapacity {
+    pub vehicle_capacity: f64,
+    /// Vehicles per unit of time, in each direction, used
+    /// when the network has no frequency for the line.
+    pub frequency: Option<f64>,
+}
+
+#[derive(Deserialize)]
+pub struct CapacityConfig {
+    #[serde(flatten)]
+    pub default: LineCapacity,
+    /// Capacity of specific lines, by line index.
+    #[serde(default)]
+    pub lines: BTreeMap<usize, LineCapacity>,
+}
+
+impl CapacityConfig {
+    /// The frequency of the line comes from *frequencies*,
+    /// the same the boarding waits are derived from, if known.
+    fn line_capacity(&self, line: usize, frequencies: &[LineFrequency]) -> MResult<f64> {
+        let conf = self.lines.get(&line).unwrap_or(&self.default);
+        let frequency = frequencies
+            .get(line)
+            .map(|f| f.frequency)
+            .or(conf.frequency)
+            .ok_or_else(|| format!("no frequency for the capacity of line {line}"))?;
+        Ok(conf.vehicle_capacity * frequency)
+    }
+}
+
+/// Passenger flow on an arc, in the direction
+/// of `arc`, and its travel time under that load.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ArcLoad {
+    pub arc: (usize, usize),
+    pub flow: f64,
+    pub time: f64,
+}
+
+/// Set the capacity of each arc in `arc_services`: the sum of the
+/// capacities of the lines running on it, each line counted once
+/// however many of its patterns (branches, express) share the arc.
+pub fn assign_capacities(net: &mut Network, conf: &CapacityConfig) -> MResult<()> {
+    let line_ids = &net.line_ids;
+    for arc in &mut net.arc_services {
+        let lines: BTreeSet<usize> = arc.services.iter().map(|p| line_ids[*p]).collect();
+        let mut capacity = 0.;
+        for line in lines {
+            capacity += conf.line_capacity(line, &net.line_frequencies)?;
+        }
+        arc.capacity = Some(capacity);
+    }
+    Ok(())
+}
+
+/// Read a demand matrix with one row per origin station.
+pub fn read_demand_csv<R: Read>(mut reader: R, stations: usize) -> MResult<Vec<Vec<f64>>> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    let mut demand = vec![];
+    for line in text.lines().filter(|l| !l.trim().is_empty()) {
+        let row = line
+            .split(',')
+            .map(|c| c.trim().parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()?;
+        if row.len() != stations {
+            let msg = format!("demand row with {} values, expected {stations}", row.len());
+            return Err(msg.into());
+        }
+        demand.push(row);
+    }
+    if demand.len() != stations {
+        let msg = format!("demand with {} rows, expected {stations}", demand.len());
+        return Err(msg.into());
+    }
+    Ok(demand)
+}
+
+/// Assign `demand` to the arcs of `graph`, filling `arc_loads`
+/// and `station_loads`: the passengers starting, ending or
+/// passing through each station. Trips take the shortest path
+/// on the `ServiceGraph`, so that boarding waits are paid as in
+/// the skim. Arcs without a capacity (e.g. walking transfers)
+/// are never congested, trips to unreachable stations are left
+/// out of the loads and counted in `unassigned_demand`.
+pub fn assign_flows(mut net: Network, method: AssignmentMethod) -> Network {
+    let service = ServiceGraph::new(&net);
+    let arcs = ArcTable::new(&net, &service);
+    let Loading {
+        mut flows,
+        unassigned,
+    } = all_or_nothing(&net, &service, &arcs, &arcs.free_times);
+    if let AssignmentMethod::Msa {
+        iterations,
+        alpha,
+        beta,
+    } = method
+    {
+        for k in 1..iterations {
+            let times = arcs.times(&flows, alpha, beta);
+            let target = all_or_nothing(&net, &service, &arcs, &times).flows;
+            let step = 1. / (k + 1) as f64;
+            for (f, t) in flows.iter_mut().zip(target) {
+                *f += step * (t - *f);
+            }
+        }
+    }
+    let times = match method {
+        AssignmentMethod::AllOrNothing => arcs.free_times.clone(),
+        AssignmentMethod::Msa { alpha, beta, .. } => arcs.times(&flows, alpha, beta),
+    };
+
+    let mut station_loads: Vec<f64> = net
+        .demand
+        .iter()
+        .zip(&unassigned)
+        .map(|(row, u)| row.iter().sum::<f64>() - u)
+        .collect();
+    for (&(_, j), flow) in arcs.arcs.iter().zip(&flows) {
+        station_loads[j] += flow;
+    }
+    net.arc_loads = arcs
+        .arcs
+        .iter()
+        .zip(flows)
+        .zip(times)
+        .map(|((arc, flow), time)| ArcLoad {
+            arc: *arc,
+            flow,
+            time,
+        })
+        .collect();
+    net.station_loads = station_loads;
+    net.unassigned_demand = Some(unassigned.iter().sum());
+    net
+}
+
+/// The distinct directed arcs between stations ridden or walked
+/// in the service graph: parallel arcs (e.g. a section shared
+/// by several patterns) are merged keeping the lowest weight.
+struct ArcTable {
+    arcs: Vec<(usize, usize)>,
+    /// Entry of each ride and walking arc of the service graph.
+    index: HashMap<(usize, usize), usize>,
+    free_times: Vec<f64>,
+    capacities: Vec<Option<f64>>,
+}
+
+impl ArcTable {
+    fn new(net: &Network, service: &ServiceGraph) -> Self {
+        let mut weights: BTreeMap<(usize, usize), f64> = BTreeMap::new();
+        for (a, b, w) in service.graph.arc_iterator() {
+            if let Some(arc) = service.sections.get(&(a, b)) {
+                let weight = weights.entry(*arc).or_insert(w);
+                *weight = weight.min(w);
+            }
+        }
+        let services: HashMap<(usize, usize), &ArcServices> =
+            net.arc_services.iter().map(|a| (a.arc, a)).collect();
+        let arcs: Vec<(usize, usize)> = weights.keys().copied().collect();
+        let capacities = arcs
+            .iter()
+            .map(|(i, j)| {
+                services
+                    .get(&(*i.min(j), *i.max(j)))
+                    .and_then(|a| a.capacity)
+            })
+            .collect();
+        let position: HashMap<(usize, usize), usize> =
+            arcs.iter().enumerate().map(|(k, a)| (*a, k)).collect();
+        Self {
+            index: service
+                .sections
+                .iter()
+                .map(|(arc, stations)| (*arc, position[stations]))
+                .collect(),
+            arcs,
+            free_times: weights.into_values().collect(),
+            capacities,
+        }
+    }
+
+    fn times(&self, flows: &[f64], alpha: f64, beta: f64) -> Vec<f64> {
+        self.free_times
+            .iter()
+            .zip(flows)
+            .zip(&self.capacities)
+            .map(|((t, flow), capacity)| match capacity {
+                Some(c) if *c > 0. => t * (1. + alpha * (flow / c).powf(beta)),
+                _ => *t,
+            })
+            .collect()
+    }
+}
+
+/// Flow on each arc of an `ArcTable`, and trips
+/// of each origin whose destination is unreachable.
+struct Loading {
+    flows: Vec<f64>,
+    unassigned: Vec<f64>,
+}
+
+/// Load the trips of each origin on the shortest path to their
+/// destination, following the predecessors back to the origin.
+fn all_or_nothing(
+    net: &Network,
+    service: &ServiceGraph,
+    arcs: &ArcTable,
+    times: &[f64],
+) -> Loading {
+    let mut flows = vec![0.; arcs.arcs.len()];
+    let mut unassigned = vec![0.; net.demand.len()];
+    for (origin, row) in net.demand.iter().enumerate() {
+        if row.iter().all(|t| *t == 0.) {
+            continue;
+        }
+        let paths = shortest_path::dijkstra_by(&service.graph, origin, |a, b, w| {
+            arcs.index.get(&(a, b)).map_or(w, |k| times[*k])
+        });
+        for (s, trips) in row.iter().enumerate().filter(|(_, t)| **t > 0.) {
+            if paths.dist[s].is_infinite() {
+                unassigned[origin] += trips;
+                continue;
+            }
+            let mut curr = s;
+            while let Some(prev) = paths.pred[curr] {
+                if let Some(k) = arcs.index.get(&(prev, curr)) {
+                    flows[*k] += trips;
+                }
+                curr = prev;
+            }
+        }
+    }
+    Loading { flows, unassigned }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::build_graph::test_network;
+
+    #[test]
+    fn test_all_or_nothing() {
+        // line 0: 0 - 1 - 2, line 1: 0 - 3 - 2, longer
+        let mut net = make_network();
+        net.demand[0][2] = 10.;
+        net.demand[1][2] = 5.;
+        let net = assign_flows(net, AssignmentMethod::AllOrNothing);
+        let load = |arc| net.arc_loads.iter().find(|l| l.arc == arc).unwrap().flow;
+        assert_eq!(load((0, 1)), 10.);
+        assert_eq!(load((1, 2)), 15.);
+        assert_eq!(load((0, 3)), 0.);
+        assert_eq!(load((2, 1)), 0.);
+        assert_eq!(net.station_loads, vec![10., 15., 15., 0.]);
+        assert_eq!(net.unassigned_demand, Some(0.));
+    }
+
+    #[test]
+    fn test_boarding_wait() {
+        // line 1 is longer, but its vehicles run more often
+        let mut net = make_network();
+        net.demand[0][2] = 10.;
+        let wait = |boarding_wait| LineFrequency {
+            frequency: 0.5 / boarding_wait,
+            boarding_wait,
+        };
+        net.line_frequencies = vec![wait(2.), wait(0.5)];
+        let net = assign_flows(net, AssignmentMethod::AllOrNothing);
+        let load = |arc| net.arc_loads.iter().find(|l| l.arc == arc).unwrap().flow;
+        assert_eq!(load((0, 1)), 0.);
+        assert_eq!(load((0, 3)), 10.);
+        assert_eq!(load((3, 2)), 10.);
+    }
+
+    #[test]
+    fn test_zero_time_arcs() {
+        // station 2 is as far as station 1, the trips must still
+        // be loaded on both arcs
+        let lines = vec![vec![0, 1, 2]];
+        let mut net = test_network(3, lines, &[(0, 1, 1.), (1, 2, 0.)]);
+        net.demand = vec![vec![0., 0., 5.], vec![0.; 3], vec![0.; 3]];
+        let net = assign_flows(net, AssignmentMethod::AllOrNothing);
+        let load = |arc| net.arc_loads.iter().find(|l| l.arc == arc).unwrap().flow;
+        assert_eq!(load((0, 1)), 5.);
+        assert_eq!(load((1, 2)), 5.);
+        assert_eq!(net.station_loads, vec![5., 5., 5.]);
+    }
+
+    #[test]
+    fn test_unassigned_demand() {
+        // station 2 is isolated
+        let mut net = test_network(3, vec![vec![0, 1]], &[(0, 1, 1.)]);
+        net.demand = vec![vec![0., 2., 3.], vec![0.; 3], vec![1., 0., 0.]];
+        let net = assign_flows(net, AssignmentMethod::AllOrNothing);
+        assert_eq!(net.station_loads, vec![2., 2., 0.]);
+        assert_eq!(net.unassigned_demand, Some(4.));
+    }
+
+    #[test]
+    fn test_capacity_once_per_line() {
+        // two patterns of line 0 share the arc 0 - 1 with line 1
+        let lines = vec![vec![0, 1, 2], vec![0, 1], vec![1, 0]];
+        let mut net = test_network(3, lines, &[(0, 1, 1.), (1, 2, 1.)]);
+        net.line_ids = vec![0, 0, 1];
+        net.assign_arc_services();
+        let conf = CapacityConfig {
+            default: LineCapacity {
+                vehicle_capacity: 10.,
+                frequency: Some(2.),
+            },
+            lines: BTreeMap::from([(
+                1,
+                LineCapacity {
+                    vehicle_capacity: 5.,
+                    frequency: Some(1.),
+                },
+            )]),
+        };
+        assign_capacities(&mut net, &conf).unwrap();
+        let capacity = |arc| {
+            let a = net.arc_services.iter().find(|a| a.arc == arc).unwrap();
+            a.capacity
+        };
+        assert_eq!(capacity((0, 1)), Some(25.));
+        assert_eq!(capacity((1, 2)), Some(20.));
+    }
+
+    #[test]
+    fn test_capacity_from_line_frequencies() {
+        let mut net = test_network(2, vec![vec![0, 1]], &[(0, 1, 1.)]);
+        net.assign_arc_services();
+        let mut conf = CapacityConfig {
+            default: LineCapacity {
+                vehicle_capacity: 10.,
+                frequency: None,
+            },
+            lines: BTreeMap::new(),
+        };
+        assert!(assign_capacities(&mut net, &conf).is_err());
+        conf.default.frequency = Some(2.);
+        net.line_frequencies = vec![LineFrequency {
+            frequency: 0.5,
+            boarding_wait: 1.,
+        }];
+        assign_capacities(&mut net, &conf).unwrap();
+        assert_eq!(net.arc_services[0].capacity, Some(5.));
+    }
+
+    #[test]
+    fn test_msa() {
+        let mut net = make_network();
+        net.demand[0][2] = 100.;
+        let conf = CapacityConfig {
+            default: LineCapacity {
+                vehicle_capacity: 10.,
+                frequency: Some(2.),
+            },
+            lines: BTreeMap::new(),
+        };
+        assign_capacities(&mut net, &conf).unwrap();
+        assert_eq!(net.arc_services[0].capacity, Some(20.));
+        let method = AssignmentMethod::Msa {
+            iterations: 50,
+            alpha: DEFAULT_BPR_ALPHA,
+            beta: DEFAULT_BPR_BETA,
+        };
+        let net = assign_flows(net, method);
+        let load = |arc| net.arc_loads.iter().find(|l| l.arc == arc).unwrap();
+        // congestion moves part of the trips on the longer line
+        assert!(load((0, 3)).flow > 0.);
+        assert!(load((0, 1)).flow < 100.);
+        assert!((load((0, 1)).flow + load((0, 3)).flow - 100.).abs() < 1e-9);
+        assert!(load((0, 1)).time > 1.);
+    }
+
+    #[test]
+    fn test_read_demand() {
+        let demand = read_demand_csv("0,1.5\n2,0\n".as_bytes(), 2).unwrap();
+        assert_eq!(demand, vec![vec![0., 1.5], vec![2., 0.]]);
+        assert!(read_demand_csv("0,1\n".as_bytes(), 2).is_err());
+        assert!(read_demand_csv("0,x\n1,0\n".as_bytes(), 2).is_err());
+    }
+
+    fn make_network() -> Network {
+        let lines = vec![vec![0, 1, 2], vec![0, 3, 2]];
+        let arcs = [(0, 1, 1.), (1, 2, 1.), (0, 3, 1.5), (3, 2, 1.5)];
+        let mut net = test_network(4, lines, &arcs);
+        net.demand = vec![vec![0.; 4]; 4];
+        net.assign_arc_services();
+        net
+    }
+}
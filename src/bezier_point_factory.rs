@@ -1,6 +1,8 @@
 pub type Point = (f64, f64);
 
+use crate::poisson_disk;
 use crate::rand_utils::random_in_range;
+use serde::Serialize;
 
 pub struct BezierPointFactory {
     orig_factory: OriginPointFactory,
@@ -8,32 +10,40 @@ pub struct BezierPointFactory {
 
 impl BezierPointFactory {
     pub fn new(config: &FactoryConfig) -> Self {
-        let orig_factory = OriginPointFactory::new(
-            config.center_radius,
-            config.point_radius,
-            config.size_x,
-            config.size_y,
-        );
+        let orig_factory = OriginPointFactory::new(config);
         Self { orig_factory }
     }
 
-    pub fn get_bezier_points(&mut self) -> BezierPoints {
-        let start = self.orig_factory.next_point();
-        let end = self.orig_factory.next_point();
-        let ctl_factory = ControlPointFactory::new(start, end);
-        let ctrl_1 = ctl_factory.next_point();
-        let ctrl_2 = ctl_factory.next_point();
-        BezierPoints {
-            start,
-            end,
-            ctrl_1,
-            ctrl_2,
-        }
+    pub fn get_bezier_points(&mut self) -> Result<BezierPoints, SamplingError> {
+        let start = self.orig_factory.next_point()?;
+        let end = self.orig_factory.next_point()?;
+        Ok(make_bezier_points(start, end))
+    }
+
+    /// Generate the points of a curve starting from a
+    /// new terminal and ending in the given point.
+    pub fn get_branch_points(&mut self, end: Point) -> Result<BezierPoints, SamplingError> {
+        let start = self.orig_factory.next_point()?;
+        Ok(make_bezier_points(start, end))
+    }
+
+    /// Forget a terminal whose curve is replaced, so that
+    /// it no longer keeps new terminals away.
+    pub fn release_terminal(&mut self, pt: Point) {
+        self.orig_factory.points.retain(|p| *p != pt);
+    }
+
+    pub fn resample_control_points(&self, points: BezierPoints) -> BezierPoints {
+        make_bezier_points(points.start, points.end)
     }
 
     pub fn reset(&mut self) {
         self.orig_factory.reset();
     }
+
+    pub fn statistics(&self) -> SamplingStatistics {
+        self.orig_factory.stats
+    }
 }
 
 pub struct BezierPoints {
@@ -48,6 +58,38 @@ pub struct FactoryConfig {
     pub point_radius: f64,
     pub size_x: f64,
     pub size_y: f64,
+    pub max_attempts: usize,
+    pub poisson_disk: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct SamplingStatistics {
+    pub accepted: usize,
+    pub rejected: usize,
+    /// Candidates rejected while filling the Poisson-disk pool.
+    pub poisson_rejected: usize,
+}
+
+#[derive(Debug)]
+pub struct SamplingError {
+    attempts: usize,
+}
+
+impl std::fmt::Display for SamplingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "no valid terminal point found in {} attempts",
+            self.attempts
+        )
+    }
+}
+
+impl std::error::Error for SamplingError {}
+
+enum CandidateSource {
+    Uniform,
+    PoissonDisk(Vec<Point>),
 }
 
 struct OriginPointFactory {
@@ -55,38 +97,78 @@ struct OriginPointFactory {
     point_radius: f64,
     size_x: f64,
     size_y: f64,
+    max_attempts: usize,
+    source: CandidateSource,
     points: Vec<Point>,
+    stats: SamplingStatistics,
 }
 
 impl OriginPointFactory {
-    fn new(center_radius: f64, point_radius: f64, size_x: f64, size_y: f64) -> Self {
-        Self {
-            center_radius,
-            point_radius,
-            size_x,
-            size_y,
+    fn new(config: &FactoryConfig) -> Self {
+        let source = if config.poisson_disk {
+            CandidateSource::PoissonDisk(vec![])
+        } else {
+            CandidateSource::Uniform
+        };
+        let mut output = Self {
+            center_radius: config.center_radius,
+            point_radius: config.point_radius,
+            size_x: config.size_x,
+            size_y: config.size_y,
+            max_attempts: config.max_attempts,
+            source,
             points: vec![],
-        }
+            stats: SamplingStatistics::default(),
+        };
+        output.fill_candidates();
+        output
     }
 
-    fn next_point(&mut self) -> Point {
-        loop {
-            let pt = self.rand_point();
+    fn next_point(&mut self) -> Result<Point, SamplingError> {
+        for _ in 0..self.max_attempts {
+            let pt = self
+                .candidate_point()
+                .ok_or_else(|| self.sampling_error())?;
             if self.valid_point(pt) {
                 self.points.push(pt);
-                return pt;
+                self.stats.accepted += 1;
+                return Ok(pt);
             }
+            self.stats.rejected += 1;
+        }
+        Err(self.sampling_error())
+    }
+
+    fn candidate_point(&mut self) -> Option<Point> {
+        match &mut self.source {
+            CandidateSource::Uniform => Some(self.rand_point()),
+            CandidateSource::PoissonDisk(pool) => pool.pop(),
+        }
+    }
+
+    fn sampling_error(&self) -> SamplingError {
+        SamplingError {
+            attempts: self.max_attempts,
         }
     }
 
     fn rand_point(&self) -> Point {
+        let (x_range, y_range) = self.ranges();
+        rand_point(x_range, y_range)
+    }
+
+    fn ranges(&self) -> ((f64, f64), (f64, f64)) {
         let x_range = (-self.size_x, self.size_x);
         let y_range = (-self.size_y, self.size_y);
-        rand_point(x_range, y_range)
+        (x_range, y_range)
     }
 
     fn valid_point(&self, pt: Point) -> bool {
-        self.accept_radius(pt) && self.accept_position(pt)
+        match self.source {
+            CandidateSource::Uniform => self.accept_radius(pt) && self.accept_position(pt),
+            // Poisson-disk candidates already respect the minimum separation.
+            CandidateSource::PoissonDisk(_) => self.accept_radius(pt),
+        }
     }
 
     fn accept_radius(&self, pt: Point) -> bool {
@@ -99,8 +181,23 @@ impl OriginPointFactory {
             .all(|ot| accpet_distance(pt, *ot, self.point_radius))
     }
 
+    fn fill_candidates(&mut self) {
+        let (x_range, y_range) = self.ranges();
+        if let CandidateSource::PoissonDisk(pool) = &mut self.source {
+            let samples = poisson_disk::poisson_disk_samples(
+                x_range,
+                y_range,
+                self.point_radius,
+                self.max_attempts,
+            );
+            *pool = samples.points;
+            self.stats.poisson_rejected += samples.rejected;
+        }
+    }
+
     fn reset(&mut self) {
         self.points.clear();
+        self.fill_candidates();
     }
 }
 
@@ -120,6 +217,18 @@ impl ControlPointFactory {
     }
 }
 
+fn make_bezier_points(start: Point, end: Point) -> BezierPoints {
+    let ctl_factory = ControlPointFactory::new(start, end);
+    let ctrl_1 = ctl_factory.next_point();
+    let ctrl_2 = ctl_factory.next_point();
+    BezierPoints {
+        start,
+        end,
+        ctrl_1,
+        ctrl_2,
+    }
+}
+
 fn get_bounds(p1: (f64, f64), p2: (f64, f64)) -> ((f64, f64), (f64, f64)) {
     let (x1, y1) = p1;
     let (x2, y2) = p2;
@@ -186,6 +295,22 @@ mod test {
         assert_eq!(y_range, (50., 100.));
     }
 
+    #[test]
+    fn test_release_terminal() {
+        let config = FactoryConfig {
+            center_radius: 0.,
+            point_radius: 1.,
+            size_x: 100.,
+            size_y: 100.,
+            max_attempts: 100,
+            poisson_disk: false,
+        };
+        let mut factory = BezierPointFactory::new(&config);
+        let points = factory.get_bezier_points().unwrap();
+        factory.release_terminal(points.start);
+        assert_eq!(factory.orig_factory.points, vec![points.end]);
+    }
+
     #[test]
     fn test_min_max() {
         let test_1 = (6.7, 13.2);
@@ -19,6 +19,137 @@ pub fn is_connected(g: &NetGraph) -> bool {
     visited.iter().all(|b| *b)
 }
 
+/// Label each node with the index of its connected component.
+pub fn components(g: &NetGraph) -> Vec<usize> {
+    let nodes = g.node_count();
+    let mut output = vec![None; nodes];
+    let mut count = 0;
+    for start in 0..nodes {
+        if output[start].is_some() {
+            continue;
+        }
+        output[start] = Some(count);
+        let mut node_queue = vec![start];
+        while let Some(i) = node_queue.pop() {
+            for j in successors(g, i) {
+                if output[j].is_none() {
+                    output[j] = Some(count);
+                    node_queue.push(j);
+                }
+            }
+        }
+        count += 1;
+    }
+    output.into_iter().flatten().collect()
+}
+
+/// Arcs and nodes whose removal increases
+/// the number of connected components.
+#[derive(Debug, Default, PartialEq)]
+pub struct GraphCuts {
+    pub bridges: Vec<(usize, usize)>,
+    pub articulation_points: Vec<usize>,
+}
+
+/// Find bridges and articulation points with Tarjan's low-link
+/// search. Parallel arcs between two nodes are never bridges.
+pub fn find_cuts(g: &NetGraph) -> GraphCuts {
+    let nodes = g.node_count();
+    let mut search = CutSearch {
+        g,
+        disc: vec![None; nodes],
+        low: vec![0; nodes],
+        time: 0,
+        cuts: GraphCuts::default(),
+    };
+    for n in 0..nodes {
+        if search.disc[n].is_none() {
+            search.visit(n);
+        }
+    }
+    let mut cuts = search.cuts;
+    cuts.bridges.sort_unstable();
+    cuts.articulation_points.sort_unstable();
+    cuts.articulation_points.dedup();
+    cuts
+}
+
+struct CutSearch<'a> {
+    g: &'a NetGraph,
+    disc: Vec<Option<usize>>,
+    low: Vec<usize>,
+    time: usize,
+    cuts: GraphCuts,
+}
+
+/// A node on the search path, its successors explored one at a time.
+struct Frame {
+    node: usize,
+    parent: Option<usize>,
+    /// The arc used to reach the node, skipped only once.
+    parent_arc: Option<usize>,
+    successors: Vec<usize>,
+    next: usize,
+    children: usize,
+}
+
+impl<'a> CutSearch<'a> {
+    /// Depth first search from *root*, on an explicit stack
+    /// so that long lines can not overflow the call stack.
+    fn visit(&mut self, root: usize) {
+        let mut stack = vec![self.enter(root, None)];
+        while let Some(frame) = stack.last_mut() {
+            let u = frame.node;
+            if let Some(v) = frame.successors.get(frame.next).copied() {
+                frame.next += 1;
+                if frame.parent_arc == Some(v) {
+                    frame.parent_arc = None;
+                    continue;
+                }
+                match self.disc[v] {
+                    Some(d) => self.low[u] = self.low[u].min(d),
+                    None => {
+                        frame.children += 1;
+                        let child = self.enter(v, Some(u));
+                        stack.push(child);
+                    }
+                }
+                continue;
+            }
+            let frame = stack.pop().unwrap();
+            let Some(p) = frame.parent else {
+                if frame.children > 1 {
+                    self.cuts.articulation_points.push(u);
+                }
+                continue;
+            };
+            let disc = self.disc[p].unwrap();
+            self.low[p] = self.low[p].min(self.low[u]);
+            if self.low[u] > disc {
+                self.cuts.bridges.push((p.min(u), p.max(u)));
+            }
+            let p_is_root = stack.last().is_some_and(|f| f.parent.is_none());
+            if !p_is_root && self.low[u] >= disc {
+                self.cuts.articulation_points.push(p);
+            }
+        }
+    }
+
+    fn enter(&mut self, node: usize, parent: Option<usize>) -> Frame {
+        self.disc[node] = Some(self.time);
+        self.low[node] = self.time;
+        self.time += 1;
+        Frame {
+            node,
+            parent,
+            parent_arc: parent,
+            successors: successors(self.g, node).collect(),
+            next: 0,
+            children: 0,
+        }
+    }
+}
+
 fn successors(g: &NetGraph, n: usize) -> impl Iterator<Item = usize> + '_ {
     g.successor_iterator(n).map(|(_, dst, _)| dst)
 }
@@ -44,6 +175,48 @@ mod test {
         assert!(!is_connected(&graph));
     }
 
+    #[test]
+    fn test_components() {
+        let mut graph = NetGraph::new_undirect(6);
+        graph.add_new_default_arc(0, 2);
+        graph.add_new_default_arc(3, 1);
+        graph.add_new_default_arc(2, 4);
+        assert_eq!(components(&graph), vec![0, 1, 0, 1, 0, 2]);
+    }
+
+    #[test]
+    fn test_find_cuts() {
+        let mut graph = NetGraph::new_undirect(7);
+        for (i, j) in [
+            (0, 1),
+            (1, 2),
+            (2, 0),
+            (2, 3),
+            (3, 4),
+            (4, 5),
+            (5, 3),
+            (5, 6),
+            (5, 6),
+        ] {
+            graph.add_new_default_arc(i, j);
+        }
+        let cuts = find_cuts(&graph);
+        assert_eq!(cuts.bridges, vec![(2, 3)]);
+        assert_eq!(cuts.articulation_points, vec![2, 3, 5]);
+    }
+
+    #[test]
+    fn test_find_cuts_long_path() {
+        let nodes = 200_000;
+        let mut graph = NetGraph::new_undirect(nodes);
+        for i in 1..nodes {
+            graph.add_new_default_arc(i - 1, i);
+        }
+        let cuts = find_cuts(&graph);
+        assert_eq!(cuts.bridges.len(), nodes - 1);
+        assert_eq!(cuts.articulation_points, (1..nodes - 1).collect::<Vec<_>>());
+    }
+
     fn make_base_graph() -> NetGraph {
         let mut graph = NetGraph::new_direct(5);
 
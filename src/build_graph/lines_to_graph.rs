@@ -2,13 +2,19 @@ use super::check_connected_graph;
 use super::{point_factory::PointListFactory, Lines, NetGraph, Network, Pt};
 use simplegraph::Graph;
 
-pub fn build_graph(pts: PointListFactory, lines: Lines) -> Option<Network> {
+pub fn build_graph(
+    pts: PointListFactory,
+    lines: Lines,
+    allow_disconnected: bool,
+) -> Option<Network> {
     let points = pts.get_points();
-    is_connected(&points, &lines).map(|graph| Network {
-        lines,
-        points,
-        graph,
-    })
+    let line_ids = (0..lines.len()).collect();
+    let graph = if allow_disconnected {
+        Some(add_arc_weights(line_to_graph(&points, &lines), &points))
+    } else {
+        is_connected(&points, &lines)
+    };
+    graph.map(|graph| Network::new(points, graph, lines, line_ids))
 }
 
 fn is_connected(pts: &[Pt], lines: &[Vec<usize>]) -> Option<NetGraph> {
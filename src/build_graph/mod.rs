@@ -1,34 +1,227 @@
-use serde::Serialize;
-use simplegraph::AdjList;
+use serde::{Deserialize, Serialize};
+use simplegraph::{AdjList, Graph};
 
+use crate::assignment::ArcLoad;
+use crate::centrality::Centrality;
 use crate::float_table::FloatMatrix;
+use crate::trial_statistics::TrialFailure;
 use crate::Curve;
 
 mod build_lines;
 mod check_connected_graph;
 mod lines_to_graph;
 mod point_factory;
+mod service_patterns;
 
-#[derive(Debug, Serialize)]
+pub use check_connected_graph::{components, find_cuts, is_connected};
+
+/// Each entry of `lines` is the station sequence of a service
+/// pattern: a line without branches has a single pattern, a
+/// branched line has one more pattern for each branch.
+/// `line_ids` maps each pattern to the line it belongs to.
+/// `tracks` is the infrastructure: the station sequence along
+/// each curve. The arcs of `graph` are the track sections,
+/// `arc_services` lists the patterns running on each of them.
+/// `express_patterns` are the indexes of the patterns skipping
+/// stations, their sections are additional arcs of `graph`.
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Network {
     pub lines: Lines,
+    pub line_ids: Vec<usize>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub express_patterns: Vec<usize>,
+    pub tracks: Lines,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub arc_services: Vec<ArcServices>,
     pub points: Vec<Pt>,
     pub graph: NetGraph,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transfers: Vec<Transfer>,
+    /// Connected component of each station, reported
+    /// only when disconnected networks are allowed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub components: Vec<usize>,
+    /// Track sections and stations whose closure splits the
+    /// network, reported when a robustness check is configured.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bridges: Vec<(usize, usize)>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub articulation_stations: Vec<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub centrality: Option<Centrality>,
+    /// Trips from each station (row) to each other station.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub demand: Vec<Vec<f64>>,
+    /// Assigned passenger flows, see `assignment`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub arc_loads: Vec<ArcLoad>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub station_loads: Vec<f64>,
+    /// Trips whose destination cannot be reached, left out of the loads.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unassigned_demand: Option<f64>,
+    /// Frequency of each line, by line index.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub line_frequencies: Vec<LineFrequency>,
+}
+
+/// Connection between two stations of different
+/// lines that are too close to be kept unrelated.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Transfer {
+    pub stations: (usize, usize),
+    pub distance: f64,
+    pub kind: TransferKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LineFrequency {
+    pub frequency: f64,
+    /// Expected wait to board a vehicle of this line.
+    pub boarding_wait: f64,
+}
+
+/// Service patterns running on the arc between two stations.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ArcServices {
+    pub arc: (usize, usize),
+    pub services: Vec<usize>,
+    /// Passengers per unit of time in each direction,
+    /// when line capacities are configured.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capacity: Option<f64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferKind {
+    /// The stations are connected by a walking arc in the graph.
+    Walking,
+    /// Passengers have to leave the system to change: the pair
+    /// is only reported, the graph is left untouched.
+    OutOfStation,
 }
 
 pub type NetGraph = AdjList<f64>;
 pub type Lines = Vec<Vec<usize>>;
 pub type Pt = (f64, f64);
 
+impl Network {
+    /// A network whose tracks are the given service patterns,
+    /// without transfers and analysis results.
+    pub fn new(points: Vec<Pt>, graph: NetGraph, lines: Lines, line_ids: Vec<usize>) -> Self {
+        Self {
+            tracks: lines.clone(),
+            lines,
+            line_ids,
+            express_patterns: vec![],
+            arc_services: vec![],
+            points,
+            graph,
+            transfers: vec![],
+            components: vec![],
+            bridges: vec![],
+            articulation_stations: vec![],
+            centrality: None,
+            demand: vec![],
+            arc_loads: vec![],
+            station_loads: vec![],
+            unassigned_demand: None,
+            line_frequencies: vec![],
+        }
+    }
+
+    /// Expected wait to board *pattern* at a station whose wait time
+    /// is *station_wait*: the boarding wait of the line when line
+    /// frequencies are known, the station wait otherwise.
+    pub fn boarding_wait(&self, pattern: usize, station_wait: f64) -> f64 {
+        self.line_frequencies
+            .get(self.line_ids[pattern])
+            .map_or(station_wait, |f| f.boarding_wait)
+    }
+
+    /// The graph of the track sections alone, without
+    /// walking transfers and express arcs.
+    pub fn track_graph(&self) -> NetGraph {
+        let mut sections = std::collections::BTreeSet::new();
+        for p in self.tracks.iter().flat_map(|t| t.windows(2)) {
+            if p[0] != p[1] {
+                sections.insert((p[0].min(p[1]), p[0].max(p[1])));
+            }
+        }
+        let mut graph = NetGraph::new_undirect(self.points.len());
+        for (i, j) in sections {
+            if let Some(w) = crate::shortest_path::arc_weight(&self.graph, i, j) {
+                graph.add_new_arc(i, j, w);
+            }
+        }
+        graph
+    }
+
+    pub fn assign_arc_services(&mut self) {
+        self.arc_services = self.compute_arc_services();
+    }
+
+    /// Patterns running on each arc according to the current
+    /// `lines`, whether `arc_services` is up to date or not.
+    pub fn compute_arc_services(&self) -> Vec<ArcServices> {
+        service_patterns::arc_services(&self.lines)
+    }
+
+    /// Return, for each station, the sorted list of
+    /// the distinct lines serving it.
+    pub fn station_lines(&self) -> Vec<Vec<usize>> {
+        let mut output = vec![vec![]; self.points.len()];
+        for (pattern, line) in self.lines.iter().zip(&self.line_ids) {
+            for s in pattern {
+                let lines: &mut Vec<usize> = &mut output[*s];
+                if let Err(idx) = lines.binary_search(line) {
+                    lines.insert(idx, *line);
+                }
+            }
+        }
+        output
+    }
+}
+
+/// Test network with *count* stations at the origin, one line
+/// for each pattern and an undirected graph with the given arcs.
+#[cfg(test)]
+pub fn test_network(count: usize, lines: Lines, arcs: &[(usize, usize, f64)]) -> Network {
+    let mut graph = NetGraph::new_undirect(count);
+    for (i, j, w) in arcs {
+        graph.add_new_arc(*i, *j, *w);
+    }
+    let line_ids = (0..lines.len()).collect();
+    Network::new(vec![(0., 0.); count], graph, lines, line_ids)
+}
+
+/// Arcs of weight *w* between the consecutive stations of *lines*,
+/// each section once.
+#[cfg(test)]
+pub fn line_arcs(lines: &[Vec<usize>], w: f64) -> Vec<(usize, usize, f64)> {
+    let mut sections = std::collections::BTreeSet::new();
+    for p in lines.iter().flat_map(|l| l.windows(2)) {
+        sections.insert((p[0].min(p[1]), p[0].max(p[1])));
+    }
+    sections.into_iter().map(|(i, j)| (i, j, w)).collect()
+}
+
 fn new_lines(line_count: usize) -> Lines {
     vec![vec![]; line_count]
 }
 
+/// Curves after the first `curves.len() - branch_lines.len()`
+/// are branches of the line given in *branch_lines*.
 pub fn build_network(
     curves: &[Curve],
     nodes: &[Vec<f64>],
     intersections: &FloatMatrix<(usize, f64)>,
-) -> Option<Network> {
+    branch_lines: &[usize],
+    allow_disconnected: bool,
+) -> Result<Network, TrialFailure> {
     let (point_factory, lines) = build_lines::build_lines(curves, nodes, intersections);
-    lines_to_graph::build_graph(point_factory, lines)
+    let net = lines_to_graph::build_graph(point_factory, lines, allow_disconnected)
+        .ok_or(TrialFailure::Disconnected)?;
+    service_patterns::merge_branches(net, branch_lines).ok_or(TrialFailure::BranchJunction)
 }
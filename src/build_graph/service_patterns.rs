@@ -0,0 +1,108 @@
+use super::{ArcServices, Lines, Network};
+use std::collections::BTreeMap;
+
+/// Replace the track of each branch with the service pattern running
+/// from the first station of the trunk to the branch terminal.
+/// The tracks of the branches are expected at the end of the line list,
+/// *branch_lines* holds, for each of them, the index of its trunk.
+pub fn merge_branches(mut net: Network, branch_lines: &[usize]) -> Option<Network> {
+    let trunks = net.lines.len() - branch_lines.len();
+    let branch_tracks = net.lines.split_off(trunks);
+    net.line_ids.truncate(trunks);
+    for (line, track) in branch_lines.iter().zip(branch_tracks) {
+        let pattern = branch_pattern(&net.lines[*line], &track)?;
+        net.lines.push(pattern);
+        net.line_ids.push(*line);
+    }
+    Some(net)
+}
+
+/// The branch track runs from its terminal to the junction on the trunk.
+fn branch_pattern(trunk: &[usize], branch: &[usize]) -> Option<Vec<usize>> {
+    let (junction, branch) = branch.split_last()?;
+    let junction = trunk.iter().position(|s| s == junction)?;
+    let pattern = trunk[..=junction]
+        .iter()
+        .chain(branch.iter().rev())
+        .copied()
+        .collect();
+    Some(pattern)
+}
+
+/// Map each pair of consecutive stations in a pattern to
+/// all the patterns running between them, in any direction.
+pub fn arc_services(lines: &Lines) -> Vec<ArcServices> {
+    let mut arcs: BTreeMap<(usize, usize), Vec<usize>> = BTreeMap::new();
+    for (k, pattern) in lines.iter().enumerate() {
+        for pair in pattern.windows(2) {
+            let (i, j) = (pair[0], pair[1]);
+            let services = arcs.entry((i.min(j), i.max(j))).or_default();
+            if services.last() != Some(&k) {
+                services.push(k);
+            }
+        }
+    }
+    arcs.into_iter()
+        .map(|(arc, services)| ArcServices {
+            arc,
+            services,
+            capacity: None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_branch_pattern() {
+        let trunk = [0, 1, 2, 3, 4];
+        let branch = [7, 6, 5, 2];
+        let pattern = branch_pattern(&trunk, &branch);
+        assert_eq!(pattern, Some(vec![0, 1, 2, 5, 6, 7]));
+
+        let branch = [7, 6, 5, 9];
+        assert_eq!(branch_pattern(&trunk, &branch), None);
+    }
+
+    #[test]
+    fn test_arc_services() {
+        let lines = vec![vec![0, 1, 2, 3], vec![4, 2, 1, 5], vec![6, 1]];
+        let result = arc_services(&lines);
+        let expected = vec![
+            ArcServices {
+                arc: (0, 1),
+                services: vec![0],
+                capacity: None,
+            },
+            ArcServices {
+                arc: (1, 2),
+                services: vec![0, 1],
+                capacity: None,
+            },
+            ArcServices {
+                arc: (1, 5),
+                services: vec![1],
+                capacity: None,
+            },
+            ArcServices {
+                arc: (1, 6),
+                services: vec![2],
+                capacity: None,
+            },
+            ArcServices {
+                arc: (2, 3),
+                services: vec![0],
+                capacity: None,
+            },
+            ArcServices {
+                arc: (2, 4),
+                services: vec![1],
+                capacity: None,
+            },
+        ];
+        assert_eq!(result, expected);
+    }
+}
@@ -0,0 +1,197 @@
+use crate::build_graph::NetGraph;
+use crate::shortest_path::QueueEntry;
+use serde::{Deserialize, Serialize};
+use simplegraph::{GetGraphType, GraphType, GraphVisitor};
+use std::collections::{BTreeMap, BinaryHeap};
+
+/// Relative tolerance used to recognize shortest paths of equal cost.
+const TIE_TOLERANCE: f64 = 1e-9;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Centrality {
+    pub stations: Vec<StationCentrality>,
+    pub arcs: Vec<ArcCentrality>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StationCentrality {
+    /// Adjacent stations over all the other stations.
+    pub degree: f64,
+    /// Reachable stations over the sum of their distances.
+    pub closeness: f64,
+    pub betweenness: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArcCentrality {
+    pub arc: (usize, usize),
+    pub betweenness: f64,
+}
+
+/// Compute the centrality of the nodes and arcs of *g*, using the
+/// arc weights as lengths. Betweenness follows Brandes' algorithm
+/// and is not normalized; in an undirected graph each pair of
+/// stations is counted once.
+pub fn centrality(g: &NetGraph) -> Centrality {
+    let nodes = g.node_count();
+    let gtype = g.graph_type();
+    let mut node_betweenness = vec![0.; nodes];
+    let mut arc_betweenness: BTreeMap<(usize, usize), f64> = g
+        .arc_iterator()
+        .filter(|(i, j, _)| i != j)
+        .map(|(i, j, _)| (arc_key(gtype, i, j), 0.))
+        .collect();
+    let mut closeness = vec![0.; nodes];
+
+    for (src, closeness) in closeness.iter_mut().enumerate() {
+        let paths = single_source(g, src);
+        let total: f64 = paths.order.iter().map(|n| paths.dist[*n]).sum();
+        if total > 0. {
+            *closeness = (paths.order.len() - 1) as f64 / total;
+        }
+        let mut delta = vec![0.; nodes];
+        for w in paths.order.iter().rev() {
+            for v in &paths.preds[*w] {
+                let c = paths.sigma[*v] / paths.sigma[*w] * (1. + delta[*w]);
+                *arc_betweenness.entry(arc_key(gtype, *v, *w)).or_default() += c;
+                delta[*v] += c;
+            }
+            if *w != src {
+                node_betweenness[*w] += delta[*w];
+            }
+        }
+    }
+
+    let scale = match gtype {
+        GraphType::Direct => 1.,
+        GraphType::Undirect => 0.5,
+    };
+    let stations = (0..nodes)
+        .map(|i| StationCentrality {
+            degree: degree(g, i),
+            closeness: closeness[i],
+            betweenness: node_betweenness[i] * scale,
+        })
+        .collect();
+    let arcs = arc_betweenness
+        .into_iter()
+        .map(|(arc, b)| ArcCentrality {
+            arc,
+            betweenness: b * scale,
+        })
+        .collect();
+    Centrality { stations, arcs }
+}
+
+struct SingleSource {
+    /// Reached nodes, by non decreasing distance.
+    order: Vec<usize>,
+    dist: Vec<f64>,
+    sigma: Vec<f64>,
+    preds: Vec<Vec<usize>>,
+}
+
+/// Dijkstra search counting the shortest paths to each node.
+fn single_source(g: &NetGraph, src: usize) -> SingleSource {
+    let nodes = g.node_count();
+    let mut dist = vec![f64::INFINITY; nodes];
+    let mut sigma = vec![0.; nodes];
+    let mut preds = vec![vec![]; nodes];
+    let mut done = vec![false; nodes];
+    let mut order = vec![];
+    let mut queue = BinaryHeap::new();
+    dist[src] = 0.;
+    sigma[src] = 1.;
+    queue.push(QueueEntry::new(src, 0.));
+
+    while let Some(QueueEntry { node, cost }) = queue.pop() {
+        if done[node] || cost > dist[node] {
+            continue;
+        }
+        done[node] = true;
+        order.push(node);
+        for (_, next, w) in g.successor_iterator(node) {
+            let next_cost = cost + w;
+            let tie = (next_cost - dist[next]).abs() <= TIE_TOLERANCE * next_cost.max(1.);
+            if next == node || done[next] {
+                continue;
+            } else if tie {
+                if !preds[next].contains(&node) {
+                    sigma[next] += sigma[node];
+                    preds[next].push(node);
+                }
+            } else if next_cost < dist[next] {
+                dist[next] = next_cost;
+                sigma[next] = sigma[node];
+                preds[next] = vec![node];
+                queue.push(QueueEntry::new(next, next_cost));
+            }
+        }
+    }
+
+    SingleSource {
+        order,
+        dist,
+        sigma,
+        preds,
+    }
+}
+
+fn degree(g: &NetGraph, i: usize) -> f64 {
+    let nodes = g.node_count();
+    if nodes < 2 {
+        return 0.;
+    }
+    let mut next: Vec<usize> = g
+        .successor_iterator(i)
+        .map(|(_, j, _)| j)
+        .filter(|j| *j != i)
+        .collect();
+    next.sort_unstable();
+    next.dedup();
+    next.len() as f64 / (nodes - 1) as f64
+}
+
+fn arc_key(gtype: GraphType, i: usize, j: usize) -> (usize, usize) {
+    match gtype {
+        GraphType::Direct => (i, j),
+        GraphType::Undirect => (i.min(j), i.max(j)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use simplegraph::Graph;
+
+    #[test]
+    fn test_path_centrality() {
+        // 0 - 1 - 2 - 3
+        let mut graph = NetGraph::new_undirect(4);
+        graph.add_new_arc(0, 1, 1.);
+        graph.add_new_arc(1, 2, 1.);
+        graph.add_new_arc(2, 3, 1.);
+        let c = centrality(&graph);
+        let betweenness: Vec<f64> = c.stations.iter().map(|s| s.betweenness).collect();
+        assert_eq!(betweenness, vec![0., 2., 2., 0.]);
+        let arcs: Vec<f64> = c.arcs.iter().map(|a| a.betweenness).collect();
+        assert_eq!(arcs, vec![3., 4., 3.]);
+        assert_eq!(c.stations[0].closeness, 0.5);
+        assert_eq!(c.stations[1].degree, 2. / 3.);
+    }
+
+    #[test]
+    fn test_equal_paths() {
+        // a square: two shortest paths between opposite corners
+        let mut graph = NetGraph::new_undirect(4);
+        graph.add_new_arc(0, 1, 1.);
+        graph.add_new_arc(1, 2, 1.);
+        graph.add_new_arc(2, 3, 1.);
+        graph.add_new_arc(3, 0, 1.);
+        let c = centrality(&graph);
+        for s in &c.stations {
+            assert_eq!(s.betweenness, 0.5);
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use crate::build_graph::{self, Network, Pt};
+use serde::{Deserialize, Serialize};
+use simplegraph::Graph;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum ConnectivityPolicy {
+    /// Discard the trials generating a disconnected network.
+    #[default]
+    #[serde(rename = "connected")]
+    Connected,
+    /// Keep disconnected networks, reporting the component of each station.
+    #[serde(rename = "disconnected")]
+    Disconnected,
+    /// Join the components with the shortest possible connector lines.
+    #[serde(rename = "auto_connect")]
+    AutoConnect,
+}
+
+impl ConnectivityPolicy {
+    pub fn allow_disconnected(self) -> bool {
+        self != Self::Connected
+    }
+}
+
+pub fn apply_connectivity(mut net: Network, policy: ConnectivityPolicy) -> Network {
+    match policy {
+        ConnectivityPolicy::Connected => net,
+        ConnectivityPolicy::Disconnected => {
+            net.components = build_graph::components(&net.graph);
+            net
+        }
+        ConnectivityPolicy::AutoConnect => auto_connect(net),
+    }
+}
+
+/// Add a two stations line between the closest stations of two
+/// components until the network is connected: the connectors
+/// form a minimum spanning tree of the components.
+fn auto_connect(mut net: Network) -> Network {
+    let mut components = build_graph::components(&net.graph);
+    let mut next_line = net.line_ids.iter().max().map_or(0, |l| l + 1);
+    while let Some((i, j, dist)) = closest_pair(&net.points, &components) {
+        let joined = components[j];
+        for c in components.iter_mut().filter(|c| **c == joined) {
+            *c = 0;
+        }
+        net.graph.add_new_arc(i, j, dist);
+        net.lines.push(vec![i, j]);
+        net.tracks.push(vec![i, j]);
+        net.line_ids.push(next_line);
+        next_line += 1;
+    }
+    net
+}
+
+/// Closest pair of stations with the first in component 0
+/// and the second in any other component.
+fn closest_pair(pts: &[Pt], components: &[usize]) -> Option<(usize, usize, f64)> {
+    let mut output: Option<(usize, usize, f64)> = None;
+    let inside = (0..pts.len()).filter(|i| components[*i] == 0);
+    for i in inside {
+        for j in (0..pts.len()).filter(|j| components[*j] != 0) {
+            let dist = distance(pts[i], pts[j]);
+            if output.is_none_or(|(_, _, d)| dist < d) {
+                output = Some((i, j, dist));
+            }
+        }
+    }
+    output
+}
+
+fn distance(p1: Pt, p2: Pt) -> f64 {
+    let (x1, y1) = p1;
+    let (x2, y2) = p2;
+    (x1 - x2).hypot(y1 - y2)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_closest_pair() {
+        let pts = [(0., 0.), (1., 0.), (5., 0.), (3., 0.), (10., 0.)];
+        let components = [0, 0, 1, 1, 2];
+        assert_eq!(closest_pair(&pts, &components), Some((1, 3, 2.)));
+        assert_eq!(closest_pair(&pts, &[0, 0, 0, 0, 0]), None);
+    }
+}
@@ -0,0 +1,153 @@
+use crate::build_graph::{Network, Pt};
+use crate::centrality;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct DemandConfig {
+    pub total_trips: f64,
+    /// Distance decay parameter of the deterrence function.
+    pub decay: f64,
+    #[serde(default)]
+    pub deterrence: Deterrence,
+    #[serde(default)]
+    pub attraction: Attraction,
+}
+
+/// How the number of trips falls with the distance d between two stations.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Deterrence {
+    /// exp(-decay * d)
+    #[default]
+    Exponential,
+    /// d ^ -decay
+    Power,
+}
+
+/// The mass of each station in the gravity model.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Attraction {
+    /// Every station is equal, only positions matter.
+    #[default]
+    Uniform,
+    /// Gaussian population density around the centre of the network.
+    Density { spread: f64 },
+    /// Closeness centrality of the station.
+    Centrality,
+}
+
+/// Fill `demand` using a gravity model: the trips between two
+/// stations are proportional to the product of their masses and
+/// to the deterrence of their straight line distance, scaled so
+/// that they sum up to the configured total.
+pub fn add_demand(mut net: Network, conf: &DemandConfig) -> Network {
+    let masses = masses(&net, conf.attraction);
+    let mut demand = vec![vec![0.; net.points.len()]; net.points.len()];
+    for (i, row) in demand.iter_mut().enumerate() {
+        for (j, trips) in row.iter_mut().enumerate().filter(|(j, _)| i != *j) {
+            let dist = distance(net.points[i], net.points[j]);
+            *trips = masses[i] * masses[j] * deterrence(conf, dist);
+        }
+    }
+    let total: f64 = demand.iter().flatten().sum();
+    if total > 0. {
+        let scale = conf.total_trips / total;
+        demand.iter_mut().flatten().for_each(|t| *t *= scale);
+    }
+    net.demand = demand;
+    net
+}
+
+fn masses(net: &Network, attraction: Attraction) -> Vec<f64> {
+    match attraction {
+        Attraction::Uniform => vec![1.; net.points.len()],
+        Attraction::Density { spread } => {
+            let center = centroid(&net.points);
+            net.points
+                .iter()
+                .map(|p| (-(distance(*p, center) / spread).powi(2) / 2.).exp())
+                .collect()
+        }
+        Attraction::Centrality => match &net.centrality {
+            Some(c) => c.stations.iter().map(|s| s.closeness).collect(),
+            None => centrality::centrality(&net.graph)
+                .stations
+                .iter()
+                .map(|s| s.closeness)
+                .collect(),
+        },
+    }
+}
+
+fn deterrence(conf: &DemandConfig, dist: f64) -> f64 {
+    match conf.deterrence {
+        Deterrence::Exponential => (-conf.decay * dist).exp(),
+        // stations in the same place would attract infinite trips
+        Deterrence::Power => dist.max(f64::EPSILON).powf(-conf.decay),
+    }
+}
+
+fn centroid(pts: &[Pt]) -> Pt {
+    let count = pts.len().max(1) as f64;
+    let (x, y) = pts
+        .iter()
+        .fold((0., 0.), |(x, y), (px, py)| (x + px, y + py));
+    (x / count, y / count)
+}
+
+fn distance(p1: Pt, p2: Pt) -> f64 {
+    let (x1, y1) = p1;
+    let (x2, y2) = p2;
+    (x1 - x2).hypot(y1 - y2)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::build_graph::test_network;
+
+    #[test]
+    fn test_gravity_demand() {
+        let net = make_network(vec![(0., 0.), (1., 0.), (3., 0.)]);
+        let conf = DemandConfig {
+            total_trips: 100.,
+            decay: 0.5,
+            deterrence: Deterrence::Exponential,
+            attraction: Attraction::Uniform,
+        };
+        let net = add_demand(net, &conf);
+        let total: f64 = net.demand.iter().flatten().sum();
+        assert!((total - 100.).abs() < 1e-9);
+        for i in 0..3 {
+            assert_eq!(net.demand[i][i], 0.);
+            for j in 0..3 {
+                assert_eq!(net.demand[i][j], net.demand[j][i]);
+            }
+        }
+        assert!(net.demand[0][1] > net.demand[0][2]);
+        let ratio = net.demand[0][1] / net.demand[0][2];
+        assert!((ratio - 1_f64.exp()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_density_attraction() {
+        let net = make_network(vec![(-4., 0.), (0., 0.), (4., 0.), (8., 0.)]);
+        let masses = masses(&net, Attraction::Density { spread: 2. });
+        // the centroid is at x = 2
+        assert_eq!(masses[1], masses[2]);
+        assert!(masses[0] < masses[1]);
+        assert_eq!(masses[0], masses[3]);
+    }
+
+    fn make_network(points: Vec<Pt>) -> Network {
+        let lines = vec![(0..points.len()).collect::<Vec<_>>()];
+        let arcs: Vec<(usize, usize, f64)> = (1..points.len())
+            .map(|k| (k - 1, k, distance(points[k - 1], points[k])))
+            .collect();
+        let mut net = test_network(points.len(), lines, &arcs);
+        net.points = points;
+        net
+    }
+}
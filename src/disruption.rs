@@ -0,0 +1,355 @@
+use crate::build_graph::Network;
+use crate::centrality;
+use crate::shortest_path;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+fn get_default_count() -> usize {
+    1
+}
+
+#[derive(Deserialize)]
+pub struct DisruptionConfig {
+    pub base_name: String,
+    pub families: Vec<DisruptionFamily>,
+}
+
+/// `count` scenarios, each closing `size` elements chosen by `target`.
+#[derive(Deserialize)]
+pub struct DisruptionFamily {
+    pub target: Target,
+    pub size: usize,
+    /// Ignored by the centrality targets, which give a single scenario.
+    #[serde(default = "get_default_count")]
+    pub count: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Target {
+    RandomStations,
+    /// The stations with the highest betweenness.
+    CentralStations,
+    RandomArcs,
+    /// The arcs with the highest betweenness.
+    CentralArcs,
+    /// Every arc served only by the closed lines.
+    Lines,
+    /// Consecutive sections of a random service pattern.
+    Segments,
+}
+
+/// A degraded copy of instance `instance`: the graph without the
+/// closed `stations` and `arcs`, compared to the original one.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Disruption {
+    pub instance: usize,
+    pub target: Target,
+    pub stations: Vec<usize>,
+    pub arcs: Vec<(usize, usize)>,
+    /// Closed lines, for the `lines` target.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub lines: Vec<usize>,
+    /// Arcs of the closed lines left open for the other lines using them.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub shared_arcs: Vec<(usize, usize)>,
+    #[serde(flatten)]
+    pub degradation: Degradation,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Degradation {
+    /// Connected components of the open stations.
+    pub components: usize,
+    /// Stations in the largest component over all the stations.
+    pub largest_component: f64,
+    /// Connected pairs of stations that are no longer connected,
+    /// pairs with a closed station included.
+    pub disconnected_pairs: f64,
+    /// Mean ratio of the new shortest path cost over the
+    /// original one, on the pairs still connected.
+    pub mean_stretch: f64,
+    /// Global efficiency (mean inverse distance) over the original one.
+    pub efficiency: f64,
+}
+
+/// Generate the disruption scenarios of each family on the
+/// graph of *net*, the network saved with id *instance*.
+pub fn disruptions(net: &Network, conf: &DisruptionConfig, instance: usize) -> Vec<Disruption> {
+    let base = all_distances(net, &Closure::default());
+    let mut output = vec![];
+    for family in &conf.families {
+        let count = match family.target {
+            Target::CentralStations | Target::CentralArcs => 1,
+            _ => family.count,
+        };
+        for _ in 0..count {
+            let closure = make_closure(net, family.target, family.size);
+            let distances = all_distances(net, &closure);
+            output.push(Disruption {
+                instance,
+                target: family.target,
+                degradation: compare(&base, &distances, &closure),
+                stations: closure.stations.into_iter().collect(),
+                arcs: closure.arcs.into_iter().collect(),
+                lines: closure.lines,
+                shared_arcs: closure.shared_arcs,
+            });
+        }
+    }
+    output
+}
+
+#[derive(Default)]
+struct Closure {
+    stations: BTreeSet<usize>,
+    /// Closed arcs as (min, max) station pairs.
+    arcs: BTreeSet<(usize, usize)>,
+    lines: Vec<usize>,
+    shared_arcs: Vec<(usize, usize)>,
+}
+
+impl Closure {
+    fn is_open(&self, i: usize, j: usize) -> bool {
+        !self.stations.contains(&i)
+            && !self.stations.contains(&j)
+            && !self.arcs.contains(&(i.min(j), i.max(j)))
+    }
+}
+
+fn make_closure(net: &Network, target: Target, size: usize) -> Closure {
+    let mut closure = Closure::default();
+    match target {
+        Target::RandomStations => {
+            closure.stations = random_subset(net.points.len(), size).into_iter().collect();
+        }
+        Target::CentralStations => {
+            let c = centrality::centrality(&net.graph);
+            let mut order: Vec<usize> = (0..c.stations.len()).collect();
+            order.sort_by(|a, b| {
+                c.stations[*b]
+                    .betweenness
+                    .total_cmp(&c.stations[*a].betweenness)
+            });
+            closure.stations = order.into_iter().take(size).collect();
+        }
+        Target::RandomArcs => {
+            let arcs = graph_arcs(net);
+            let subset = random_subset(arcs.len(), size);
+            closure.arcs = subset.into_iter().map(|k| arcs[k]).collect();
+        }
+        Target::CentralArcs => {
+            let mut arcs = centrality::centrality(&net.graph).arcs;
+            arcs.sort_by(|a, b| b.betweenness.total_cmp(&a.betweenness));
+            closure.arcs = arcs.into_iter().take(size).map(|a| a.arc).collect();
+        }
+        Target::Lines => {
+            let line_count = net.line_ids.iter().max().map_or(0, |l| l + 1);
+            let mut lines = random_subset(line_count, size);
+            lines.sort_unstable();
+            for a in net.compute_arc_services() {
+                let closed = |p: &usize| lines.contains(&net.line_ids[*p]);
+                if a.services.iter().all(closed) {
+                    closure.arcs.insert(a.arc);
+                } else if a.services.iter().any(closed) {
+                    closure.shared_arcs.push(a.arc);
+                }
+            }
+            closure.lines = lines;
+        }
+        Target::Segments => {
+            if !net.lines.is_empty() {
+                let pattern = &net.lines[fastrand::usize(..net.lines.len())];
+                let sections = pattern.len().saturating_sub(1);
+                let size = size.min(sections);
+                let start = fastrand::usize(..=sections - size);
+                closure.arcs = pattern[start..start + size + 1]
+                    .windows(2)
+                    .map(|p| (p[0].min(p[1]), p[0].max(p[1])))
+                    .collect();
+            }
+        }
+    }
+    closure
+}
+
+/// The distinct arcs of the graph between two different stations.
+fn graph_arcs(net: &Network) -> Vec<(usize, usize)> {
+    let arcs: BTreeSet<(usize, usize)> = net
+        .graph
+        .arc_iterator()
+        .filter(|(i, j, _)| i != j)
+        .map(|(i, j, _)| (i.min(j), i.max(j)))
+        .collect();
+    arcs.into_iter().collect()
+}
+
+fn random_subset(len: usize, size: usize) -> Vec<usize> {
+    let mut items: Vec<usize> = (0..len).collect();
+    fastrand::shuffle(&mut items);
+    items.truncate(size);
+    items
+}
+
+/// Shortest path costs between all the stations, infinite from
+/// and to the closed stations.
+fn all_distances(net: &Network, closure: &Closure) -> Vec<Vec<f64>> {
+    let nodes = net.points.len();
+    (0..nodes)
+        .map(|src| {
+            if closure.stations.contains(&src) {
+                return vec![f64::INFINITY; nodes];
+            }
+            shortest_path::dijkstra_by(&net.graph, src, |i, j, w| {
+                if closure.is_open(i, j) {
+                    w
+                } else {
+                    f64::INFINITY
+                }
+            })
+            .dist
+        })
+        .collect()
+}
+
+fn compare(base: &[Vec<f64>], distances: &[Vec<f64>], closure: &Closure) -> Degradation {
+    let nodes = base.len();
+    let mut connected = 0;
+    let mut disconnected = 0;
+    let mut stretch = 0.;
+    let mut stretch_pairs = 0;
+    let mut base_efficiency = 0.;
+    let mut efficiency = 0.;
+    for i in 0..nodes {
+        for j in (0..nodes).filter(|j| *j != i) {
+            let (before, after) = (base[i][j], distances[i][j]);
+            if before.is_infinite() {
+                continue;
+            }
+            connected += 1;
+            if before > 0. {
+                base_efficiency += 1. / before;
+            }
+            if after.is_infinite() {
+                disconnected += 1;
+            } else if before > 0. {
+                stretch += after / before;
+                stretch_pairs += 1;
+                efficiency += 1. / after;
+            }
+        }
+    }
+
+    let mut component = vec![None; nodes];
+    let mut sizes = vec![];
+    for i in (0..nodes).filter(|i| !closure.stations.contains(i)) {
+        if component[i].is_none() {
+            for j in (0..nodes).filter(|j| distances[i][*j].is_finite()) {
+                component[j] = Some(sizes.len());
+            }
+            sizes.push(distances[i].iter().filter(|d| d.is_finite()).count());
+        }
+    }
+
+    let ratio = |a: f64, b: f64| if b > 0. { a / b } else { 0. };
+    Degradation {
+        components: sizes.len(),
+        largest_component: ratio(sizes.into_iter().max().unwrap_or(0) as f64, nodes as f64),
+        disconnected_pairs: ratio(disconnected as f64, connected as f64),
+        mean_stretch: ratio(stretch, stretch_pairs as f64),
+        efficiency: ratio(efficiency, base_efficiency),
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::build_graph::{line_arcs, test_network};
+
+    #[test]
+    fn test_closed_arc() {
+        // a square 0 - 1 - 2 - 3 - 0
+        let net = make_network();
+        let closure = Closure {
+            arcs: BTreeSet::from([(0, 1)]),
+            ..Default::default()
+        };
+        let base = all_distances(&net, &Closure::default());
+        let d = compare(&base, &all_distances(&net, &closure), &closure);
+        assert_eq!(d.components, 1);
+        assert_eq!(d.largest_component, 1.);
+        assert_eq!(d.disconnected_pairs, 0.);
+        // 0 - 1 grows from 1 to 3, 0 - 2 and 1 - 3 stay at 2
+        assert!((d.mean_stretch - (3. + 1. + 1. + 1. + 1. + 1.) / 6.).abs() < 1e-12);
+        assert!(d.efficiency < 1.);
+    }
+
+    #[test]
+    fn test_closed_stations() {
+        let net = make_network();
+        let closure = Closure {
+            stations: BTreeSet::from([0, 2]),
+            ..Default::default()
+        };
+        let base = all_distances(&net, &Closure::default());
+        let d = compare(&base, &all_distances(&net, &closure), &closure);
+        assert_eq!(d.components, 2);
+        assert_eq!(d.largest_component, 0.25);
+        assert_eq!(d.disconnected_pairs, 1.);
+        assert_eq!(d.efficiency, 0.);
+    }
+
+    #[test]
+    fn test_disruptions() {
+        let net = make_network();
+        let family = |target, size, count| DisruptionFamily {
+            target,
+            size,
+            count,
+        };
+        let conf = DisruptionConfig {
+            base_name: String::new(),
+            families: vec![
+                family(Target::RandomStations, 1, 3),
+                family(Target::CentralArcs, 2, 3),
+                family(Target::Lines, 1, 2),
+                family(Target::Segments, 5, 1),
+            ],
+        };
+        let output = disruptions(&net, &conf, 7);
+        assert_eq!(output.len(), 3 + 1 + 2 + 1);
+        assert!(output.iter().all(|d| d.instance == 7));
+        assert_eq!(output[0].stations.len(), 1);
+        assert_eq!(output[3].arcs.len(), 2);
+        for d in &output[4..6] {
+            // each line runs on two sections of its own
+            assert_eq!(d.lines.len(), 1);
+            assert_eq!(d.arcs.len(), 2);
+        }
+        // a segment can not be longer than its pattern
+        assert_eq!(output[6].arcs.len(), 2);
+    }
+
+    #[test]
+    fn test_shared_arcs() {
+        // line 1 shares the section 1 - 2 with line 0; arc
+        // services are left stale on purpose
+        let lines = vec![vec![0, 1, 2], vec![1, 2, 3]];
+        let net = test_network(4, lines.clone(), &line_arcs(&lines, 1.));
+        let closure = make_closure(&net, Target::Lines, 1);
+        if closure.lines == vec![0] {
+            assert_eq!(closure.arcs, BTreeSet::from([(0, 1)]));
+        } else {
+            assert_eq!(closure.arcs, BTreeSet::from([(2, 3)]));
+        }
+        assert_eq!(closure.shared_arcs, vec![(1, 2)]);
+    }
+
+    fn make_network() -> Network {
+        let lines = vec![vec![0, 1, 2], vec![2, 3, 0]];
+        let mut net = test_network(4, lines.clone(), &line_arcs(&lines, 1.));
+        net.assign_arc_services();
+        net
+    }
+}
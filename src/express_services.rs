@@ -0,0 +1,150 @@
+use crate::build_graph::{NetGraph, Network, TransferKind};
+use crate::rand_utils::random_in_range;
+use crate::shortest_path;
+use crate::MResult;
+use serde::Deserialize;
+use simplegraph::Graph;
+
+#[derive(Deserialize)]
+pub struct ExpressConfig {
+    /// Fraction of the lines getting an express pattern.
+    pub fraction: f64,
+    /// Probability for an ordinary station to be an express stop.
+    pub stop_probability: f64,
+    /// Ratio between the express and the local travel time.
+    pub speed_factor: f64,
+}
+
+/// Overlay an express pattern on a random subset of the lines.
+/// Express patterns stop at terminals, interchanges and at random
+/// stations; each skipping section becomes a new, faster arc. A
+/// section between two stations already linked by an arc keeps that
+/// arc, so that other patterns never ride at the express time.
+pub fn add_express_services(mut net: Network, conf: &ExpressConfig) -> MResult<Network> {
+    let interchanges = interchanges(&net);
+    for pattern in select_patterns(&net.line_ids, conf.fraction) {
+        let stops = express_stops(&net.lines[pattern], &interchanges, conf.stop_probability);
+        if stops.len() == net.lines[pattern].len() {
+            continue;
+        }
+        let line = &net.lines[pattern];
+        let arcs = express_arcs(&net.graph, line, &stops, conf.speed_factor)?;
+        for (i, j, w) in arcs {
+            net.graph.add_new_arc(i, j, w);
+        }
+        let stops = stops.into_iter().map(|k| line[k]).collect();
+        net.express_patterns.push(net.lines.len());
+        net.lines.push(stops);
+        net.line_ids.push(net.line_ids[pattern]);
+    }
+    Ok(net)
+}
+
+/// Return the first pattern of a random *fraction* of the lines.
+fn select_patterns(line_ids: &[usize], fraction: f64) -> Vec<usize> {
+    let mut firsts: Vec<usize> = (0..line_ids.len())
+        .filter(|k| !line_ids[..*k].contains(&line_ids[*k]))
+        .collect();
+    fastrand::shuffle(&mut firsts);
+    let count = (fraction * firsts.len() as f64).round() as usize;
+    firsts.truncate(count);
+    firsts.sort_unstable();
+    firsts
+}
+
+fn interchanges(net: &Network) -> Vec<bool> {
+    let mut output: Vec<bool> = net.station_lines().iter().map(|l| l.len() > 1).collect();
+    for transfer in &net.transfers {
+        if transfer.kind == TransferKind::Walking {
+            let (i, j) = transfer.stations;
+            output[i] = true;
+            output[j] = true;
+        }
+    }
+    output
+}
+
+/// Positions, in the pattern, of the express stops.
+fn express_stops(pattern: &[usize], interchanges: &[bool], stop_probability: f64) -> Vec<usize> {
+    let last = pattern.len().saturating_sub(1);
+    (0..pattern.len())
+        .filter(|k| {
+            *k == 0
+                || *k == last
+                || interchanges[pattern[*k]]
+                || random_in_range((0., 1.)) < stop_probability
+        })
+        .collect()
+}
+
+fn express_arcs(
+    graph: &NetGraph,
+    pattern: &[usize],
+    stops: &[usize],
+    speed_factor: f64,
+) -> MResult<Vec<(usize, usize, f64)>> {
+    let mut output = vec![];
+    for w in stops.windows(2).filter(|w| w[1] - w[0] > 1) {
+        let section = &pattern[w[0]..=w[1]];
+        let (first, last) = (section[0], section[section.len() - 1]);
+        if shortest_path::arc_weight(graph, first, last).is_some() {
+            continue;
+        }
+        let mut cost = 0.;
+        for a in section.windows(2) {
+            cost += shortest_path::arc_weight(graph, a[0], a[1])
+                .ok_or_else(|| format!("no arc from {} to {}", a[0], a[1]))?;
+        }
+        output.push((first, last, cost * speed_factor));
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_express_stops() {
+        let pattern = [0, 1, 2, 3, 4, 5, 6];
+        let mut interchanges = vec![false; 7];
+        interchanges[3] = true;
+        let stops = express_stops(&pattern, &interchanges, 0.);
+        assert_eq!(stops, vec![0, 3, 6]);
+        let stops = express_stops(&pattern, &interchanges, 1.);
+        assert_eq!(stops, vec![0, 1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn test_express_arcs() {
+        let mut graph = NetGraph::new_undirect(5);
+        graph.add_new_arc(0, 1, 1.);
+        graph.add_new_arc(1, 2, 2.);
+        graph.add_new_arc(2, 3, 3.);
+        graph.add_new_arc(3, 4, 4.);
+        let arcs = express_arcs(&graph, &[0, 1, 2, 3, 4], &[0, 1, 4], 0.5).unwrap();
+        assert_eq!(arcs, vec![(1, 4, 4.5)]);
+    }
+
+    #[test]
+    fn test_express_arcs_keep_existing_arcs() {
+        let mut graph = NetGraph::new_undirect(4);
+        graph.add_new_arc(0, 1, 1.);
+        graph.add_new_arc(1, 2, 1.);
+        graph.add_new_arc(2, 3, 1.);
+        // another pattern already runs directly from 0 to 2
+        graph.add_new_arc(0, 2, 3.);
+        let arcs = express_arcs(&graph, &[0, 1, 2, 3], &[0, 2, 3], 0.5).unwrap();
+        assert!(arcs.is_empty());
+        assert!(express_arcs(&graph, &[0, 1, 3], &[0, 2], 0.5).is_err());
+    }
+
+    #[test]
+    fn test_select_patterns() {
+        let line_ids = [0, 1, 2, 0, 3, 2];
+        assert_eq!(select_patterns(&line_ids, 1.), vec![0, 1, 2, 4]);
+        assert!(select_patterns(&line_ids, 0.).is_empty());
+        assert_eq!(select_patterns(&line_ids, 0.5).len(), 2);
+    }
+}
@@ -43,6 +43,12 @@ impl<T> FloatTable<T> {
     }
 }
 
+impl<T> Default for FloatTable<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod test {
 
@@ -0,0 +1,415 @@
+use crate::build_graph::{self, NetGraph, Network, Transfer, TransferKind};
+use crate::shortest_path;
+use crate::MResult;
+use serde::{Deserialize, Serialize};
+use simplegraph::Graph;
+use std::collections::HashSet;
+
+#[derive(Deserialize)]
+pub struct GrowthConfig {
+    pub core_lines: usize,
+}
+
+#[derive(Serialize)]
+pub struct GrowthStage {
+    /// Position of the stage in the growth sequence.
+    pub stage: usize,
+    /// Index, in the complete network, of each station in this stage.
+    pub station_ids: Vec<usize>,
+    #[serde(flatten)]
+    pub network: Network,
+}
+
+#[derive(Default)]
+pub struct Growth {
+    pub stages: Vec<GrowthStage>,
+    /// Stages left out because their network is disconnected.
+    pub skipped: Vec<usize>,
+}
+
+/// Active stations of a pattern, as an inclusive range of positions.
+type Range = Option<(usize, usize)>;
+
+/// Simulate the growth of *net* : start from the central sections
+/// of a few connected lines, then at each stage either extend a
+/// terminal by one section or open a new line, until the complete
+/// network is built. Stations keep the index they have in *net*,
+/// stored in `station_ids`.
+pub fn grow_network(net: &Network, conf: &GrowthConfig) -> MResult<Growth> {
+    let mut output = Growth::default();
+    if net.lines.is_empty() {
+        return Ok(output);
+    }
+    let mut plan = GrowthPlan::new(net, conf.core_lines);
+    for stage in 0.. {
+        let (station_ids, network) = snapshot(net, &plan.ranges)?;
+        if build_graph::is_connected(&network.graph) {
+            output.stages.push(GrowthStage {
+                stage,
+                station_ids,
+                network,
+            });
+        } else {
+            output.skipped.push(stage);
+        }
+        if !plan.next_stage() {
+            break;
+        }
+    }
+    Ok(output)
+}
+
+enum Step {
+    ExtendStart(usize),
+    ExtendEnd(usize),
+    OpenPattern(usize),
+    OpenLine(usize),
+}
+
+struct GrowthPlan<'a> {
+    net: &'a Network,
+    station_lines: Vec<Vec<usize>>,
+    ranges: Vec<Range>,
+    active_lines: Vec<bool>,
+}
+
+impl<'a> GrowthPlan<'a> {
+    fn new(net: &'a Network, core_lines: usize) -> Self {
+        let line_count = net.line_ids.iter().max().map_or(0, |l| l + 1);
+        let mut output = Self {
+            net,
+            station_lines: net.station_lines(),
+            ranges: vec![None; net.lines.len()],
+            active_lines: vec![false; line_count],
+        };
+        let core = output.core_lines(core_lines);
+        for line in &core {
+            output.active_lines[*line] = true;
+            let trunk = output.trunk(*line);
+            output.ranges[trunk] = Some(output.core_range(trunk, &core));
+        }
+        output
+    }
+
+    /// Choose a random set of lines, each one crossing at least another.
+    fn core_lines(&self, count: usize) -> Vec<usize> {
+        let line_count = self.active_lines.len();
+        if line_count == 0 {
+            return vec![];
+        }
+        let mut core = vec![fastrand::usize(..line_count)];
+        while core.len() < count {
+            let candidates: Vec<usize> = (0..line_count)
+                .filter(|l| !core.contains(l))
+                .filter(|l| self.crosses(*l, &core))
+                .collect();
+            if candidates.is_empty() {
+                break;
+            }
+            core.push(candidates[fastrand::usize(..candidates.len())]);
+        }
+        core
+    }
+
+    fn crosses(&self, line: usize, others: &[usize]) -> bool {
+        self.station_lines
+            .iter()
+            .any(|l| l.contains(&line) && others.iter().any(|o| l.contains(o)))
+    }
+
+    /// The section of the trunk between its first and
+    /// last interchange with the other core lines.
+    fn core_range(&self, trunk: usize, core: &[usize]) -> (usize, usize) {
+        let line = self.net.line_ids[trunk];
+        let pattern = &self.net.lines[trunk];
+        let inter: Vec<usize> = (0..pattern.len())
+            .filter(|k| {
+                let lines = &self.station_lines[pattern[*k]];
+                core.iter().any(|c| *c != line && lines.contains(c))
+            })
+            .collect();
+        match (inter.first(), inter.last()) {
+            // a single interchange: keep the two adjacent sections
+            (Some(first), Some(last)) if first == last => {
+                (first.saturating_sub(1), (last + 1).min(pattern.len() - 1))
+            }
+            (Some(first), Some(last)) => (*first, *last),
+            _ => (pattern.len() / 3, 2 * pattern.len() / 3),
+        }
+    }
+
+    fn trunk(&self, line: usize) -> usize {
+        self.net.line_ids.iter().position(|l| *l == line).unwrap()
+    }
+
+    fn next_stage(&mut self) -> bool {
+        let steps = self.available_steps();
+        if steps.is_empty() {
+            return false;
+        }
+        self.apply(&steps[fastrand::usize(..steps.len())]);
+        true
+    }
+
+    fn apply(&mut self, step: &Step) {
+        match *step {
+            Step::ExtendStart(p) => {
+                let (start, end) = self.ranges[p].unwrap();
+                self.ranges[p] = Some((start - 1, end));
+            }
+            Step::ExtendEnd(p) => {
+                let (start, end) = self.ranges[p].unwrap();
+                self.ranges[p] = Some((start, end + 1));
+            }
+            Step::OpenPattern(p) => self.open_pattern(p),
+            Step::OpenLine(line) => {
+                self.active_lines[line] = true;
+                for p in 0..self.ranges.len() {
+                    if self.net.line_ids[p] == line {
+                        self.open_pattern(p);
+                    }
+                }
+            }
+        }
+    }
+
+    fn open_pattern(&mut self, p: usize) {
+        self.ranges[p] = Some((0, self.net.lines[p].len() - 1));
+    }
+
+    fn available_steps(&self) -> Vec<Step> {
+        let active = self.active_stations();
+        let mut steps = vec![];
+        for (p, range) in self.ranges.iter().enumerate() {
+            let line = self.net.line_ids[p];
+            let last = self.net.lines[p].len() - 1;
+            match range {
+                Some((start, end)) => {
+                    if *start > 0 {
+                        steps.push(Step::ExtendStart(p));
+                    }
+                    if *end < last {
+                        steps.push(Step::ExtendEnd(p));
+                    }
+                }
+                // branches open once their trunk is complete
+                None if self.active_lines[line] => {
+                    let trunk = self.trunk(line);
+                    if self.ranges[trunk] == Some((0, self.net.lines[trunk].len() - 1)) {
+                        steps.push(Step::OpenPattern(p));
+                    }
+                }
+                None => {}
+            }
+        }
+        for (line, is_active) in self.active_lines.iter().enumerate() {
+            if !is_active && self.touches(line, &active) {
+                steps.push(Step::OpenLine(line));
+            }
+        }
+        steps
+    }
+
+    fn active_stations(&self) -> HashSet<usize> {
+        let mut output = HashSet::new();
+        for (pattern, range) in self.net.lines.iter().zip(&self.ranges) {
+            if let Some((start, end)) = range {
+                output.extend(pattern[*start..=*end].iter().copied());
+            }
+        }
+        output
+    }
+
+    fn touches(&self, line: usize, active: &HashSet<usize>) -> bool {
+        let walking: Vec<&Transfer> = walking_transfers(self.net).collect();
+        self.net
+            .lines
+            .iter()
+            .zip(&self.net.line_ids)
+            .filter(|(_, l)| **l == line)
+            .flat_map(|(pattern, _)| pattern.iter())
+            .any(|s| {
+                active.contains(s)
+                    || walking.iter().any(|t| {
+                        let (i, j) = t.stations;
+                        (i == *s && active.contains(&j)) || (j == *s && active.contains(&i))
+                    })
+            })
+    }
+}
+
+fn walking_transfers(net: &Network) -> impl Iterator<Item = &Transfer> {
+    net.transfers
+        .iter()
+        .filter(|t| t.kind == TransferKind::Walking)
+}
+
+/// Build the network made of the active part of each pattern.
+fn snapshot(net: &Network, ranges: &[Range]) -> MResult<(Vec<usize>, Network)> {
+    let mut patterns = vec![];
+    let mut line_ids = vec![];
+    for ((pattern, line), range) in net.lines.iter().zip(&net.line_ids).zip(ranges) {
+        if let Some((start, end)) = range {
+            patterns.push(&pattern[*start..=*end]);
+            line_ids.push(*line);
+        }
+    }
+
+    let mut station_ids: Vec<usize> = patterns.iter().flat_map(|p| p.iter().copied()).collect();
+    station_ids.sort_unstable();
+    station_ids.dedup();
+    let mut local = vec![None; net.points.len()];
+    for (i, s) in station_ids.iter().enumerate() {
+        local[*s] = Some(i);
+    }
+    let to_local = |s: &usize| local[*s].unwrap();
+
+    let mut graph = NetGraph::new_undirect(station_ids.len());
+    let mut added = HashSet::new();
+    for pattern in &patterns {
+        for pair in pattern.windows(2) {
+            let (i, j) = (pair[0], pair[1]);
+            if i != j && added.insert((i.min(j), i.max(j))) {
+                let w = shortest_path::arc_weight(&net.graph, i, j)
+                    .ok_or_else(|| format!("no arc from {i} to {j}"))?;
+                graph.add_new_arc(to_local(&i), to_local(&j), w);
+            }
+        }
+    }
+    let weights: Vec<f64> = net.graph.node_iterator().map(|(_, w)| w).collect();
+    graph.update_all_nodes_weight(|i, _| weights[station_ids[i]]);
+
+    let mut transfers = vec![];
+    for t in &net.transfers {
+        let (i, j) = t.stations;
+        if let (Some(li), Some(lj)) = (local[i], local[j]) {
+            if t.kind == TransferKind::Walking {
+                graph.add_new_arc(li, lj, t.distance);
+            }
+            transfers.push(Transfer {
+                stations: (li, lj),
+                distance: t.distance,
+                kind: t.kind,
+            });
+        }
+    }
+
+    let lines: Vec<Vec<usize>> = patterns
+        .iter()
+        .map(|p| p.iter().map(to_local).collect())
+        .collect();
+    let points = station_ids.iter().map(|s| net.points[*s]).collect();
+    let mut network = Network::new(points, graph, lines, line_ids);
+    network.tracks = active_tracks(&net.tracks, &added)
+        .iter()
+        .map(|t| t.iter().map(to_local).collect())
+        .collect();
+    network.transfers = transfers;
+    Ok((station_ids, network))
+}
+
+/// The parts of *tracks* made of consecutive active sections.
+fn active_tracks(tracks: &[Vec<usize>], active: &HashSet<(usize, usize)>) -> Vec<Vec<usize>> {
+    let mut output = vec![];
+    for track in tracks {
+        let mut part: Vec<usize> = vec![];
+        for pair in track.windows(2) {
+            let (i, j) = (pair[0], pair[1]);
+            if active.contains(&(i.min(j), i.max(j))) {
+                if part.is_empty() {
+                    part.push(i);
+                }
+                part.push(j);
+            } else if !part.is_empty() {
+                output.push(std::mem::take(&mut part));
+            }
+        }
+        if !part.is_empty() {
+            output.push(part);
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::build_graph::{line_arcs, test_network};
+    use crate::shortest_path;
+    use simplegraph::GraphVisitor;
+
+    #[test]
+    fn test_grow_network() {
+        let net = make_network();
+        let growth = grow_network(&net, &GrowthConfig { core_lines: 2 }).unwrap();
+        let stages = growth.stages;
+        assert!(stages.len() > 2);
+        assert!(growth.skipped.is_empty());
+
+        let mut prev: Vec<usize> = vec![];
+        for (k, stage) in stages.iter().enumerate() {
+            assert_eq!(stage.stage, k);
+            assert!(build_graph::is_connected(&stage.network.graph));
+            assert!(prev.iter().all(|s| stage.station_ids.contains(s)));
+            for (local, id) in stage.station_ids.iter().enumerate() {
+                assert_eq!(stage.network.points[local], net.points[*id]);
+            }
+            for track in &stage.network.tracks {
+                for pair in track.windows(2) {
+                    let arc = shortest_path::arc_weight(&stage.network.graph, pair[0], pair[1]);
+                    assert!(arc.is_some());
+                }
+            }
+            prev = stage.station_ids.clone();
+        }
+        let last = stages.last().unwrap();
+        assert_eq!(last.station_ids, (0..net.points.len()).collect::<Vec<_>>());
+        assert_eq!((&last.network.graph).arc_count(), (&net.graph).arc_count());
+        assert_eq!(last.network.tracks, net.tracks);
+    }
+
+    #[test]
+    fn test_extend_one_section() {
+        let net = make_network();
+        let mut plan = GrowthPlan::new(&net, 1);
+        plan.ranges = vec![Some((2, 3)), None, None, None];
+        plan.apply(&Step::ExtendStart(0));
+        assert_eq!(plan.ranges[0], Some((1, 3)));
+        plan.apply(&Step::ExtendEnd(0));
+        assert_eq!(plan.ranges[0], Some((1, 4)));
+    }
+
+    #[test]
+    fn test_no_lines() {
+        let net = test_network(2, vec![], &[]);
+        let growth = grow_network(&net, &GrowthConfig { core_lines: 2 }).unwrap();
+        assert!(growth.stages.is_empty());
+    }
+
+    #[test]
+    fn test_missing_arc() {
+        let net = test_network(3, vec![vec![0, 1, 2]], &[(0, 1, 1.)]);
+        assert!(grow_network(&net, &GrowthConfig { core_lines: 1 }).is_err());
+    }
+
+    fn make_network() -> Network {
+        let lines = vec![
+            vec![0, 1, 2, 3, 4, 5],
+            vec![6, 7, 2, 8, 9],
+            vec![10, 8, 11, 4, 12],
+            vec![0, 1, 2, 3, 13, 14],
+        ];
+        let mut net = test_network(15, lines.clone(), &line_arcs(&lines, 1.));
+        net.line_ids = vec![0, 1, 2, 0];
+        // the branch of line 0 runs on its own track from station 3
+        net.tracks = vec![
+            vec![0, 1, 2, 3, 4, 5],
+            vec![6, 7, 2, 8, 9],
+            vec![10, 8, 11, 4, 12],
+            vec![3, 13, 14],
+        ];
+        net.points = (0..15).map(|i| (i as f64, 0.)).collect();
+        net
+    }
+}
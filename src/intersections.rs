@@ -1,6 +1,13 @@
 use crate::float_table::FloatMatrix;
+use crate::self_intersections::{self, CurveDefect};
+use crate::trial_statistics::TrialFailure;
 use crate::Curve;
-use flo_curves::bezier;
+use flo_curves::{bezier, Coordinate};
+use serde::{Deserialize, Serialize};
+
+/// Tolerance used to recognize, among the intersections
+/// found numerically, the junction of a branch with its trunk.
+const JUNCTION_TOLERANCE: f64 = 1e-3;
 
 #[derive(Debug)]
 pub struct Intersections {
@@ -8,56 +15,148 @@ pub struct Intersections {
     pub inverse_intersections: FloatMatrix<(usize, f64)>,
 }
 
-pub fn make_intersection_lists(curves: &[Curve]) -> Option<Intersections> {
-    let mut output = find_all_intersections(curves);
-    sort_all(&mut output.direct_intersections)?;
-    Some(output)
+/// A branch curve ends (t = 1) on its trunk curve at the given position.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Junction {
+    pub trunk: usize,
+    pub branch: usize,
+    pub position: f64,
 }
 
-fn find_all_intersections(curves: &[Curve]) -> Intersections {
+pub fn make_intersection_lists(
+    curves: &[Curve],
+    junctions: &[Junction],
+    min_crossing_angle: Option<f64>,
+    self_crossings: bool,
+    allow_isolated: bool,
+) -> Result<Intersections, TrialFailure> {
+    let mut output = find_all_intersections(curves, junctions, min_crossing_angle.unwrap_or(0.))?;
+    if self_crossings {
+        push_all_self_intersections(&mut output, curves, min_crossing_angle.unwrap_or(0.))?;
+    }
+    sort_all(&mut output.direct_intersections, allow_isolated)
+        .ok_or(TrialFailure::NoIntersections)?;
+    Ok(output)
+}
+
+fn find_all_intersections(
+    curves: &[Curve],
+    junctions: &[Junction],
+    min_crossing_angle: f64,
+) -> Result<Intersections, TrialFailure> {
     let mut direct_intersections = vec![vec![]; curves.len()];
     let mut inverse_intersections = FloatMatrix::new(curves.len());
+    let mut min_angle = f64::INFINITY;
     all_cross_iterator(curves, |c1, c2| {
-        push_intersections(
+        let junction = junctions
+            .iter()
+            .find(|j| j.trunk == c1.0 && j.branch == c2.0);
+        let angle = push_intersections(
             &mut direct_intersections,
             &mut inverse_intersections,
             c1,
             c2,
+            junction,
         );
+        min_angle = min_angle.min(angle);
     });
 
-    Intersections {
+    if min_angle < min_crossing_angle {
+        return Err(TrialFailure::ShallowCrossing);
+    }
+
+    Ok(Intersections {
         direct_intersections,
         inverse_intersections,
-    }
+    })
 }
 
 type IndexCurve<'a> = (usize, &'a Curve);
+/// Store all intersections between the two curves and
+/// return the shallowest crossing angle found, in degrees.
 fn push_intersections<'a>(
     direct_intersections: &mut [Vec<f64>],
     inverse_intersections: &mut FloatMatrix<(usize, f64)>,
     c1: IndexCurve<'a>,
     c2: IndexCurve<'a>,
-) {
+    junction: Option<&Junction>,
+) -> f64 {
     let (i, c1) = c1;
     let (j, c2) = c2;
     let inters = bezier::curve_intersects_curve_clip(c1, c2, 1e-6);
+    let mut min_angle = f64::INFINITY;
+    if let Some(junction) = junction {
+        direct_intersections[i].push(junction.position);
+        direct_intersections[j].push(1.);
+        inverse_intersections.insert(j, 1., (i, junction.position));
+    }
     for (t1, t2) in inters {
+        if junction.is_some() && t2 > 1. - JUNCTION_TOLERANCE {
+            continue;
+        }
         direct_intersections[i].push(t1);
         direct_intersections[j].push(t2);
         inverse_intersections.insert(j, t2, (i, t1));
         inverse_intersections.insert(i, t1, (j, t1));
+        min_angle = min_angle.min(crossing_angle((c1, t1), (c2, t2)));
+    }
+    min_angle
+}
+
+/// Turn the point where a line crosses itself
+/// into a station visited twice by that line.
+fn push_all_self_intersections(
+    inter: &mut Intersections,
+    curves: &[Curve],
+    min_crossing_angle: f64,
+) -> Result<(), TrialFailure> {
+    for (i, c) in curves.iter().enumerate() {
+        match self_intersections::find_defect(c) {
+            Some(CurveDefect::Loop(t1, t2)) => {
+                if crossing_angle((c, t1), (c, t2)) < min_crossing_angle {
+                    return Err(TrialFailure::ShallowCrossing);
+                }
+                inter.direct_intersections[i].push(t1);
+                inter.direct_intersections[i].push(t2);
+                inter.inverse_intersections.insert(i, t2, (i, t1));
+            }
+            Some(CurveDefect::Cusp(_)) => return Err(TrialFailure::SelfIntersection),
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+fn crossing_angle(p1: (&Curve, f64), p2: (&Curve, f64)) -> f64 {
+    let (c1, t1) = p1;
+    let (c2, t2) = p2;
+    let d1 = bezier::Tangent::from(c1).tangent(t1);
+    let d2 = bezier::Tangent::from(c2).tangent(t2);
+    vector_angle((d1.get(0), d1.get(1)), (d2.get(0), d2.get(1)))
+}
+
+/// Angle in degrees, between 0 and 90, of the
+/// two lines with the given directions.
+fn vector_angle(v1: (f64, f64), v2: (f64, f64)) -> f64 {
+    let (x1, y1) = v1;
+    let (x2, y2) = v2;
+    let norm = (x1.hypot(y1)) * (x2.hypot(y2));
+    if norm == 0. {
+        return 0.;
     }
+    let cos = ((x1 * x2) + (y1 * y2)).abs() / norm;
+    cos.min(1.).acos().to_degrees()
 }
 
-fn sort_all(lists: &mut Vec<Vec<f64>>) -> Option<()> {
+fn sort_all(lists: &mut Vec<Vec<f64>>, allow_isolated: bool) -> Option<()> {
     for list in lists {
-        if list.is_empty() {
+        if list.is_empty() && !allow_isolated {
             return None;
         }
         list.push(0.);
         list.push(1.);
         list.sort_by(|a, b| cmp_f64(*a, *b));
+        list.dedup();
     }
     Some(())
 }
@@ -72,6 +171,33 @@ fn cmp_f64(a: f64, b: f64) -> std::cmp::Ordering {
     }
 }
 
+/// Return the curves outside the largest group of
+/// curves connected by crossings or junctions.
+pub fn disconnected_curves(curves: &[Curve], junctions: &[Junction]) -> Vec<usize> {
+    let mut component: Vec<usize> = (0..curves.len()).collect();
+    all_cross_iterator(curves, |(i, c1), (j, c2)| {
+        let joined = junctions.iter().any(|k| k.trunk == i && k.branch == j);
+        if joined || !bezier::curve_intersects_curve_clip(c1, c2, 1e-6).is_empty() {
+            merge_components(&mut component, i, j);
+        }
+    });
+    let mut sizes = vec![0; curves.len()];
+    for c in &component {
+        sizes[*c] += 1;
+    }
+    let largest = (0..sizes.len()).max_by_key(|c| sizes[*c]);
+    (0..curves.len())
+        .filter(|i| Some(component[*i]) != largest)
+        .collect()
+}
+
+fn merge_components(component: &mut [usize], i: usize, j: usize) {
+    let (from, to) = (component[j], component[i]);
+    for c in component.iter_mut().filter(|c| **c == from) {
+        *c = to;
+    }
+}
+
 fn all_cross_iterator<F, T>(slice: &[T], mut f: F)
 where
     F: FnMut((usize, &T), (usize, &T)),
@@ -89,6 +215,17 @@ mod test {
 
     use super::*;
 
+    #[test]
+    fn test_disconnected_curves() {
+        let curves = [
+            straight_line((0., 0.), (10., 10.)),
+            straight_line((0., 10.), (10., 0.)),
+            straight_line((20., 0.), (30., 0.)),
+            straight_line((8., 0.), (8., 10.)),
+        ];
+        assert_eq!(disconnected_curves(&curves, &[]), vec![2]);
+    }
+
     #[test]
     fn test_sort_f64_vec() {
         let mut vec = vec![0.45, 0.12, 0.56, 0.89, 0.23];
@@ -97,6 +234,57 @@ mod test {
         assert_eq!(result, vec);
     }
 
+    #[test]
+    fn test_vector_angle() {
+        assert!((vector_angle((1., 0.), (0., 2.)) - 90.).abs() < 1e-9);
+        assert!((vector_angle((1., 1.), (-1., 0.)) - 45.).abs() < 1e-9);
+        assert!(vector_angle((1., 1.), (-2., -2.)).abs() < 1e-3);
+        assert_eq!(vector_angle((0., 0.), (1., 0.)), 0.);
+    }
+
+    #[test]
+    fn test_shallow_crossing() {
+        let curves = vec![
+            straight_line((-10., 0.), (10., 0.)),
+            straight_line((-10., -1.), (10., 1.)),
+        ];
+        assert!(make_intersection_lists(&curves, &[], Some(2.), false, false).is_ok());
+        assert!(matches!(
+            make_intersection_lists(&curves, &[], Some(10.), false, false),
+            Err(TrialFailure::ShallowCrossing)
+        ));
+    }
+
+    #[test]
+    fn test_branch_junction() {
+        let curves = vec![
+            straight_line((-10., 0.), (10., 0.)),
+            straight_line((0., 10.), (0., 0.)),
+        ];
+        let junctions = [Junction {
+            trunk: 0,
+            branch: 1,
+            position: 0.5,
+        }];
+        let inter = make_intersection_lists(&curves, &junctions, None, false, false).unwrap();
+        assert_eq!(inter.direct_intersections[0], vec![0., 0.5, 1.]);
+        assert_eq!(inter.direct_intersections[1], vec![0., 1.]);
+        assert_eq!(inter.inverse_intersections.get(1, 1.), Some(&(0, 0.5)));
+    }
+
+    fn straight_line(p1: (f64, f64), p2: (f64, f64)) -> Curve {
+        use flo_curves::{BezierCurveFactory, Coord2};
+        let (p1, p2): (Coord2, Coord2) = (p1.into(), p2.into());
+        bezier::Curve::from_points(
+            p1,
+            (
+                p1 * (2. / 3.) + p2 * (1. / 3.),
+                p1 * (1. / 3.) + p2 * (2. / 3.),
+            ),
+            p2,
+        )
+    }
+
     #[test]
     fn test_all_cross_iter() {
         let list = ['a', 'b', 'c'];
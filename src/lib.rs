@@ -0,0 +1,41 @@
+use flo_curves::bezier;
+use flo_curves::Coord2;
+
+pub type Curve = bezier::Curve<Coord2>;
+
+pub mod all_direct_path;
+pub mod assignment;
+pub mod bezier_point_factory;
+pub mod build_graph;
+pub mod centrality;
+pub mod connectivity;
+pub mod demand;
+pub mod disruption;
+pub mod express_services;
+mod float_table;
+pub mod growth;
+pub mod intersections;
+pub mod line_edit;
+pub mod line_planning;
+pub mod make_curves;
+pub mod network_layout;
+pub mod network_targets;
+pub mod node_locations;
+pub mod pesp;
+mod poisson_disk;
+mod rand_utils;
+pub mod robustness;
+pub mod routing;
+pub mod scenarios;
+pub mod self_intersections;
+mod service_graph;
+pub mod shared_services;
+mod shortest_path;
+pub mod skim;
+pub mod station_clearance;
+pub mod station_wait_times;
+pub mod stats;
+pub mod time_expanded;
+pub mod trial_statistics;
+
+pub type MResult<T> = Result<T, Box<dyn std::error::Error>>;
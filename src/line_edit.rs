@@ -0,0 +1,239 @@
+use crate::build_graph::Pt;
+use crate::network_layout::Layout;
+use serde::Deserialize;
+use std::fmt;
+
+/// Edit of a curve of a [Layout]: *line* is the
+/// curve index, lines first and then branches.
+#[derive(Debug, Deserialize)]
+pub enum LineEdit {
+    /// Continue the curve beyond *terminal* for *length* times
+    /// its parameter range, adding *stations* new stations.
+    #[serde(rename = "extend")]
+    Extend {
+        line: usize,
+        terminal: Terminal,
+        length: f64,
+        stations: usize,
+    },
+    /// Keep only the part of the curve between *start* and *end*.
+    #[serde(rename = "truncate")]
+    Truncate { line: usize, start: f64, end: f64 },
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum Terminal {
+    #[serde(rename = "start")]
+    Start,
+    #[serde(rename = "end")]
+    End,
+}
+
+#[derive(Debug)]
+pub enum EditError {
+    UnknownLine(usize),
+    InvalidRange,
+    /// The edit would move the end of a branch away from its trunk.
+    BranchJunction,
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownLine(line) => write!(f, "line {line} does not exist"),
+            Self::InvalidRange => write!(f, "invalid extension or truncation range"),
+            Self::BranchJunction => write!(f, "edit moves a branch junction"),
+        }
+    }
+}
+
+impl std::error::Error for EditError {}
+
+pub fn apply_edit(layout: &mut Layout, edit: &LineEdit) -> Result<(), EditError> {
+    match *edit {
+        LineEdit::Extend {
+            line,
+            terminal,
+            length,
+            stations,
+        } => extend(layout, line, terminal, length, stations),
+        LineEdit::Truncate { line, start, end } => truncate(layout, line, start, end),
+    }
+}
+
+fn extend(
+    layout: &mut Layout,
+    line: usize,
+    terminal: Terminal,
+    length: f64,
+    stations: usize,
+) -> Result<(), EditError> {
+    check_line(layout, line)?;
+    if length <= 0. || stations == 0 {
+        return Err(EditError::InvalidRange);
+    }
+    let range = match terminal {
+        Terminal::Start => (-length, 1.),
+        Terminal::End if is_branch(layout, line) => return Err(EditError::BranchJunction),
+        Terminal::End => (0., 1. + length),
+    };
+    reparametrize(layout, line, range);
+
+    // the old terminal becomes an ordinary station
+    let step = length / (1. + length) / stations as f64;
+    let new_stations = match terminal {
+        Terminal::Start => (0..=stations).map(|k| k as f64 * step).collect::<Vec<_>>(),
+        Terminal::End => (0..stations)
+            .map(|k| 1. - (stations - k) as f64 * step)
+            .collect(),
+    };
+    let list = &mut layout.stations[line];
+    list.extend(new_stations);
+    list.sort_by(f64::total_cmp);
+    Ok(())
+}
+
+fn truncate(layout: &mut Layout, line: usize, start: f64, end: f64) -> Result<(), EditError> {
+    check_line(layout, line)?;
+    if !(0. ..1.).contains(&start) || end <= start || end > 1. {
+        return Err(EditError::InvalidRange);
+    }
+    let junctions = layout.junctions.iter();
+    let moves_junction = junctions
+        .filter(|j| j.trunk == line)
+        .any(|j| j.position < start || j.position > end);
+    if moves_junction || (is_branch(layout, line) && end < 1.) {
+        return Err(EditError::BranchJunction);
+    }
+    let list = &mut layout.stations[line];
+    list.retain(|t| (start..=end).contains(t));
+    // the cut becomes the new terminal
+    if start > 0. && list.first() != Some(&start) {
+        list.insert(0, start);
+    }
+    reparametrize(layout, line, (start, end));
+    Ok(())
+}
+
+fn check_line(layout: &Layout, line: usize) -> Result<(), EditError> {
+    if line < layout.curves.len() {
+        Ok(())
+    } else {
+        Err(EditError::UnknownLine(line))
+    }
+}
+
+fn is_branch(layout: &Layout, line: usize) -> bool {
+    layout.junctions.iter().any(|j| j.branch == line)
+}
+
+/// Replace the curve with its part between the given parameters,
+/// which may lie outside [0, 1], and move its stations and the
+/// junctions on it accordingly.
+fn reparametrize(layout: &mut Layout, line: usize, range: (f64, f64)) {
+    let (a, b) = range;
+    let map = |t: f64| (t - a) / (b - a);
+    layout.curves[line] = sub_curve(&layout.curves[line], a, b);
+    for t in &mut layout.stations[line] {
+        *t = map(*t);
+    }
+    for j in layout.junctions.iter_mut().filter(|j| j.trunk == line) {
+        j.position = map(j.position);
+    }
+}
+
+/// Control points of the cubic curve restricted (or extended)
+/// to [a, b], computed by evaluating its blossom.
+fn sub_curve(points: &[Pt; 4], a: f64, b: f64) -> [Pt; 4] {
+    [[a, a, a], [a, a, b], [a, b, b], [b, b, b]].map(|t| blossom(points, t))
+}
+
+fn blossom(points: &[Pt; 4], params: [f64; 3]) -> Pt {
+    let mut pts = points.to_vec();
+    for t in params {
+        pts = pts
+            .windows(2)
+            .map(|w| {
+                let ((x1, y1), (x2, y2)) = (w[0], w[1]);
+                (x1 + (x2 - x1) * t, y1 + (y2 - y1) * t)
+            })
+            .collect();
+    }
+    pts[0]
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::intersections::Junction;
+
+    const POINTS: [Pt; 4] = [(0., 0.), (1., 3.), (3., 3.), (4., 0.)];
+
+    #[test]
+    fn test_sub_curve() {
+        assert_eq!(sub_curve(&POINTS, 0., 1.), POINTS);
+        let extended = sub_curve(&POINTS, 0., 2.);
+        let back = sub_curve(&extended, 0., 0.5);
+        for (p, q) in back.iter().zip(POINTS.iter()) {
+            assert!((p.0 - q.0).abs() < 1e-9 && (p.1 - q.1).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_extend_and_truncate() {
+        let mut layout = make_layout();
+        let edit = LineEdit::Extend {
+            line: 0,
+            terminal: Terminal::End,
+            length: 1.,
+            stations: 2,
+        };
+        apply_edit(&mut layout, &edit).unwrap();
+        assert_eq!(layout.stations[0], vec![0.25, 0.5, 0.75]);
+        assert_eq!(layout.junctions[0].position, 0.25);
+
+        let edit = LineEdit::Truncate {
+            line: 0,
+            start: 0.25,
+            end: 0.75,
+        };
+        apply_edit(&mut layout, &edit).unwrap();
+        assert_eq!(layout.stations[0], vec![0., 0.5, 1.]);
+
+        // a cut between two stations keeps a terminal
+        let edit = LineEdit::Truncate {
+            line: 1,
+            start: 0.25,
+            end: 1.,
+        };
+        apply_edit(&mut layout, &edit).unwrap();
+        assert_eq!(layout.stations[1], vec![0., (0.5 - 0.25) / 0.75]);
+
+        let edit = LineEdit::Extend {
+            line: 1,
+            terminal: Terminal::End,
+            length: 1.,
+            stations: 1,
+        };
+        assert!(matches!(
+            apply_edit(&mut layout, &edit),
+            Err(EditError::BranchJunction)
+        ));
+    }
+
+    fn make_layout() -> Layout {
+        Layout {
+            curves: vec![POINTS, POINTS],
+            stations: vec![vec![0.5], vec![0.5]],
+            junctions: vec![Junction {
+                trunk: 0,
+                branch: 1,
+                position: 0.5,
+            }],
+            branch_lines: vec![0],
+            self_crossings: false,
+            connectivity: Default::default(),
+        }
+    }
+}
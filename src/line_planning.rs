@@ -0,0 +1,221 @@
+use crate::build_graph::{NetGraph, Network, Pt};
+use crate::shortest_path;
+use serde::{Deserialize, Serialize};
+use simplegraph::Graph;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+#[derive(Deserialize)]
+pub struct LinePlanningConfig {
+    pub base_name: String,
+    /// Candidate lines added between each pair of terminals.
+    pub k_shortest: usize,
+    pub fixed_cost: f64,
+    pub length_cost: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Section {
+    pub stations: (usize, usize),
+    pub length: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LineSource {
+    /// A service pattern of the generated network.
+    Generated,
+    /// A shortest path between two terminals.
+    KShortest,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CandidateLine {
+    pub stations: Vec<usize>,
+    /// Index of the section between each pair of consecutive stations.
+    pub sections: Vec<usize>,
+    pub length: f64,
+    pub cost: f64,
+    pub source: LineSource,
+}
+
+/// The infrastructure, the line pool and the demand:
+/// the input of a line planning problem.
+#[derive(Debug, Serialize)]
+pub struct LinePlanningInstance {
+    pub stations: Vec<Pt>,
+    pub sections: Vec<Section>,
+    pub lines: Vec<CandidateLine>,
+    pub demand: Vec<Vec<f64>>,
+}
+
+/// Build a line planning instance from *net*: the sections are the
+/// tracks, the pool holds the service patterns that run on them
+/// (express patterns excluded) and the *k* shortest paths between
+/// each pair of terminals. Each line costs a fixed amount plus
+/// an amount for each unit of length.
+pub fn line_planning_instance(net: &Network, conf: &LinePlanningConfig) -> LinePlanningInstance {
+    let sections = sections(net);
+    let mut infrastructure = NetGraph::new_undirect(net.points.len());
+    for s in &sections {
+        let (i, j) = s.stations;
+        infrastructure.add_new_arc(i, j, s.length);
+    }
+    let index: BTreeMap<(usize, usize), usize> = sections
+        .iter()
+        .enumerate()
+        .map(|(k, s)| (s.stations, k))
+        .collect();
+
+    let mut pool = LinePool {
+        conf,
+        index: &index,
+        sections: &sections,
+        lines: vec![],
+        known: HashSet::new(),
+    };
+    let patterns: Vec<&Vec<usize>> = net
+        .lines
+        .iter()
+        .enumerate()
+        .filter(|(p, _)| !net.express_patterns.contains(p))
+        .map(|(_, pattern)| pattern)
+        .collect();
+    for pattern in &patterns {
+        pool.add(pattern.to_vec(), LineSource::Generated);
+    }
+    let terminals: BTreeSet<usize> = patterns
+        .iter()
+        .filter_map(|p| p.first().zip(p.last()))
+        .flat_map(|(a, b)| [*a, *b])
+        .collect();
+    for a in &terminals {
+        for b in terminals.range(a + 1..) {
+            for (path, _) in
+                shortest_path::k_shortest_paths(&infrastructure, *a, *b, conf.k_shortest)
+            {
+                pool.add(path, LineSource::KShortest);
+            }
+        }
+    }
+
+    let lines = pool.lines;
+    LinePlanningInstance {
+        stations: net.points.clone(),
+        sections,
+        lines,
+        demand: net.demand.clone(),
+    }
+}
+
+struct LinePool<'a> {
+    conf: &'a LinePlanningConfig,
+    index: &'a BTreeMap<(usize, usize), usize>,
+    sections: &'a [Section],
+    lines: Vec<CandidateLine>,
+    /// Station sequences already in the pool, in both directions.
+    known: HashSet<Vec<usize>>,
+}
+
+impl<'a> LinePool<'a> {
+    fn add(&mut self, stations: Vec<usize>, source: LineSource) {
+        let sections: Option<Vec<usize>> = stations
+            .windows(2)
+            .map(|p| self.index.get(&(p[0].min(p[1]), p[0].max(p[1]))).copied())
+            .collect();
+        let sections = match sections {
+            Some(sections) if !sections.is_empty() => sections,
+            _ => return,
+        };
+        let reversed: Vec<usize> = stations.iter().rev().copied().collect();
+        if self.known.contains(&stations) || self.known.contains(&reversed) {
+            return;
+        }
+        self.known.insert(stations.clone());
+        let length: f64 = sections.iter().map(|s| self.sections[*s].length).sum();
+        self.lines.push(CandidateLine {
+            stations,
+            sections,
+            length,
+            cost: self.conf.fixed_cost + self.conf.length_cost * length,
+            source,
+        });
+    }
+}
+
+/// The distinct track sections, with the weight of their arc.
+fn sections(net: &Network) -> Vec<Section> {
+    let pairs: BTreeSet<(usize, usize)> = net
+        .tracks
+        .iter()
+        .flat_map(|t| t.windows(2))
+        .filter(|p| p[0] != p[1])
+        .map(|p| (p[0].min(p[1]), p[0].max(p[1])))
+        .collect();
+    pairs
+        .into_iter()
+        .filter_map(|(i, j)| {
+            let length = shortest_path::arc_weight(&net.graph, i, j)?;
+            Some(Section {
+                stations: (i, j),
+                length,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::build_graph::{line_arcs, test_network};
+
+    #[test]
+    fn test_line_planning_instance() {
+        let net = make_network();
+        let instance = line_planning_instance(&net, &make_config(2));
+        assert_eq!(instance.sections.len(), 4);
+        // both shortest paths between 0 and 2 are already generated lines
+        let sources: Vec<LineSource> = instance.lines.iter().map(|l| l.source).collect();
+        assert_eq!(sources, vec![LineSource::Generated, LineSource::Generated]);
+        let line = &instance.lines[1];
+        assert_eq!(line.stations, vec![2, 3, 0]);
+        assert_eq!(line.sections, vec![3, 1]);
+        assert_eq!(line.cost, 14.);
+    }
+
+    #[test]
+    fn test_express_patterns_excluded() {
+        let mut net = make_network();
+        net.express_patterns = vec![1];
+        let instance = line_planning_instance(&net, &make_config(2));
+        let lines: Vec<(&Vec<usize>, LineSource)> = instance
+            .lines
+            .iter()
+            .map(|l| (&l.stations, l.source))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                (&vec![0, 1, 2], LineSource::Generated),
+                (&vec![0, 3, 2], LineSource::KShortest)
+            ]
+        );
+    }
+
+    fn make_config(k_shortest: usize) -> LinePlanningConfig {
+        LinePlanningConfig {
+            base_name: String::new(),
+            k_shortest,
+            fixed_cost: 10.,
+            length_cost: 2.,
+        }
+    }
+
+    fn make_network() -> Network {
+        // a square 0 - 1 - 2 - 3 - 0 with lines 0 - 1 - 2 and 2 - 3 - 0
+        let lines = vec![vec![0, 1, 2], vec![2, 3, 0]];
+        let mut net = test_network(4, lines.clone(), &line_arcs(&lines, 1.));
+        net.demand = vec![vec![1.; 4]; 4];
+        net
+    }
+}
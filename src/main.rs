@@ -1,32 +1,44 @@
-use flo_curves::bezier;
-use flo_curves::Coord2;
-
 use simplegraph::dot;
 
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use structopt::StructOpt;
 
 use std::fs::File;
-use std::io::Write;
+use std::io::{BufWriter, Write};
 use std::path::PathBuf;
 
-type Curve = bezier::Curve<Coord2>;
-
-mod all_direct_path;
-mod bezier_point_factory;
-mod build_graph;
-mod float_table;
-mod intersections;
-mod make_curves;
-mod node_locations;
-mod rand_utils;
-mod station_wait_times;
-
-type MResult<T> = Result<T, Box<dyn std::error::Error>>;
+use random_metro_network::self_intersections::SelfIntersectionPolicy;
+use random_metro_network::trial_statistics::{TrialFailure, TrialStatistics};
+use random_metro_network::{
+    all_direct_path, assignment, bezier_point_factory, build_graph, centrality, connectivity,
+    demand, disruption, express_services, growth, intersections, line_edit, line_planning,
+    make_curves, network_layout, network_targets, node_locations, pesp, robustness, routing,
+    scenarios, self_intersections, shared_services, skim, station_clearance, station_wait_times,
+    stats, time_expanded,
+};
+use random_metro_network::{Curve, MResult};
 
 #[derive(StructOpt)]
 struct Arguments {
-    file: PathBuf,
+    file: Option<PathBuf>,
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(StructOpt)]
+enum Command {
+    /// Extend or truncate the lines of a saved layout
+    Edit { layout: PathBuf, edits: PathBuf },
+    /// Find the journeys between two stations of a saved network
+    Route {
+        network: PathBuf,
+        origin: usize,
+        destination: usize,
+        /// One of time, transfers or pareto
+        #[structopt(long, default_value = "pareto")]
+        criteria: routing::Criteria,
+    },
 }
 
 #[derive(Deserialize)]
@@ -37,8 +49,18 @@ enum SaveFormat {
     SaveJson(String),
 }
 
+impl SaveFormat {
+    fn with_suffix(&self, suffix: &str) -> Self {
+        match self {
+            Self::SaveYaml(name) => Self::SaveYaml(format!("{name}-{suffix}")),
+            Self::SaveJson(name) => Self::SaveJson(format!("{name}-{suffix}")),
+        }
+    }
+}
+
 const DEFAULT_TRIALS: usize = 100;
 const DEFAULT_COUNT: usize = 1;
+const DEFAULT_SAMPLING_ATTEMPTS: usize = 10000;
 
 fn get_default_trials() -> usize {
     DEFAULT_TRIALS
@@ -48,6 +70,10 @@ fn get_default_count() -> usize {
     DEFAULT_COUNT
 }
 
+fn get_default_sampling_attempts() -> usize {
+    DEFAULT_SAMPLING_ATTEMPTS
+}
+
 #[derive(Deserialize)]
 struct Configuration {
     width: f64,
@@ -55,14 +81,41 @@ struct Configuration {
     origin_distance: f64,
     points_distance: f64,
     lines: Vec<usize>,
+    #[serde(default)]
+    branches: Vec<make_curves::BranchConfig>,
     #[serde(default = "get_default_trials")]
     trials: usize,
     #[serde(default = "get_default_count")]
     count: usize,
+    #[serde(default = "get_default_sampling_attempts")]
+    sampling_attempts: usize,
+    repair_attempts: Option<usize>,
+    connectivity: Option<connectivity::ConnectivityPolicy>,
+    robustness: Option<robustness::Robustness>,
+    poisson_disk: Option<bool>,
+    min_crossing_angle: Option<f64>,
+    self_intersection: Option<self_intersections::SelfIntersectionPolicy>,
+    station_clearance: Option<station_clearance::StationClearanceConfig>,
+    targets: Option<network_targets::NetworkTargets>,
     save_option: Option<SaveFormat>,
     export_graph: Option<String>,
     station_wait: Option<station_wait_times::StationWaitTimeConfig>,
+    frequencies: Option<station_wait_times::FrequencyConfig>,
+    shared_services: Option<usize>,
+    express: Option<express_services::ExpressConfig>,
     all_direct_path: Option<bool>,
+    save_layout: Option<bool>,
+    growth: Option<growth::GrowthConfig>,
+    stats: Option<String>,
+    skim: Option<skim::SkimFormat>,
+    centrality: Option<bool>,
+    demand: Option<demand::DemandConfig>,
+    assignment: Option<assignment::AssignmentConfig>,
+    pesp: Option<pesp::PespConfig>,
+    time_expanded: Option<time_expanded::TimeExpandedConfig>,
+    line_planning: Option<line_planning::LinePlanningConfig>,
+    scenarios: Option<scenarios::ScenarioConfig>,
+    disruption: Option<disruption::DisruptionConfig>,
 }
 
 impl Configuration {
@@ -72,7 +125,48 @@ impl Configuration {
             point_radius: self.points_distance,
             size_x: self.width,
             size_y: self.height,
+            max_attempts: self.sampling_attempts,
+            poisson_disk: self.poisson_disk.unwrap_or(false),
+        }
+    }
+
+    /// Station count of each curve: lines first, then branches.
+    fn station_counts(&self) -> Vec<usize> {
+        let branches = self.branches.iter().map(|b| b.stations);
+        self.lines.iter().copied().chain(branches).collect()
+    }
+
+    fn branch_lines(&self) -> Vec<usize> {
+        self.branches.iter().map(|b| b.line).collect()
+    }
+
+    fn validate(&self) -> MResult<()> {
+        if let Some(b) = self.branches.iter().find(|b| b.line >= self.lines.len()) {
+            let msg = format!(
+                "branch of line {} but only {} lines",
+                b.line,
+                self.lines.len()
+            );
+            return Err(msg.into());
+        }
+        if self.poisson_disk == Some(true) && self.points_distance <= 0. {
+            return Err("poisson_disk sampling requires a positive points_distance".into());
+        }
+        if self.station_wait.is_some() && self.frequencies.is_some() {
+            return Err("station_wait and frequencies are alternative wait models".into());
         }
+        if self.time_expanded.is_some() && self.frequencies.is_none() {
+            return Err("the time expanded graph requires line frequencies".into());
+        }
+        if let Some(conf) = &self.assignment {
+            if conf.demand_file.is_none() && self.demand.is_none() {
+                return Err("flow assignment requires demand or demand_file".into());
+            }
+        }
+        if self.line_planning.is_some() && self.demand.is_none() {
+            return Err("the line planning instance requires demand".into());
+        }
+        Ok(())
     }
 }
 
@@ -95,35 +189,127 @@ impl TrialCounter {
     }
 }
 
+/// When the curves leave some line isolated, resample only the
+/// lines outside the main group, up to `repair_attempts` times.
 fn try_build_network(
     bpf: &mut bezier_point_factory::BezierPointFactory,
-    lines: &[usize],
-) -> Option<build_graph::Network> {
-    let curves = make_curves::make_curves(bpf, lines.len());
-    let inter = intersections::make_intersection_lists(&curves)?;
-    let nodes = node_locations::generate_node_lists(inter.direct_intersections, lines);
-    build_graph::build_network(&curves, &nodes, &inter.inverse_intersections)
+    config: &Configuration,
+    stats: &mut TrialStatistics,
+) -> Result<NetworkWithLayout, TrialFailure> {
+    let policy = config.self_intersection;
+    let mut curves = make_curves::make_curves(bpf, config.lines.len(), policy)?;
+    let mut junctions =
+        make_curves::make_branch_curves(bpf, &mut curves, &config.branches, policy)?;
+    let mut repairs = config.repair_attempts.unwrap_or(0);
+    loop {
+        match build_from_curves(&curves, &junctions, config) {
+            Err(TrialFailure::NoIntersections | TrialFailure::Disconnected) if repairs > 0 => {
+                let lines = intersections::disconnected_curves(&curves, &junctions);
+                if lines.is_empty() {
+                    return Err(TrialFailure::Disconnected);
+                }
+                make_curves::resample_curves(bpf, &mut curves, &mut junctions, &lines, policy)?;
+                stats.repairs += lines.len();
+                repairs -= 1;
+            }
+            result => return result,
+        }
+    }
 }
 
-fn build_network(
-    factory_config: &bezier_point_factory::FactoryConfig,
-    trials: usize,
-    lines: &[usize],
-) -> Option<build_graph::Network> {
-    let mut bezier_points_factory = bezier_point_factory::BezierPointFactory::new(factory_config);
-    let mut trials = TrialCounter::new(trials);
+fn build_from_curves(
+    curves: &[Curve],
+    junctions: &[intersections::Junction],
+    config: &Configuration,
+) -> Result<NetworkWithLayout, TrialFailure> {
+    let policy = config.self_intersection;
+    let self_crossings = policy == Some(SelfIntersectionPolicy::Station);
+    let connectivity = config.connectivity.unwrap_or_default();
+    let inter = intersections::make_intersection_lists(
+        curves,
+        junctions,
+        config.min_crossing_angle,
+        self_crossings,
+        connectivity.allow_disconnected(),
+    )?;
+    let counts = config.station_counts();
+    let direct = inter.direct_intersections;
+    let nodes = node_locations::generate_node_lists(direct.clone(), &counts);
+    let net = build_graph::build_network(
+        curves,
+        &nodes,
+        &inter.inverse_intersections,
+        &config.branch_lines(),
+        connectivity.allow_disconnected(),
+    )?;
+    let net = apply_clearance_if_required(net, &config.station_clearance)?;
+    let net = connectivity::apply_connectivity(net, connectivity);
+    let net = apply_robustness_if_required(net, config.robustness)?;
+    if let Some(targets) = &config.targets {
+        targets.check(&net)?;
+    }
+    let layout = config.save_layout.unwrap_or(false).then(|| {
+        network_layout::Layout::new(
+            curves,
+            &nodes,
+            &direct,
+            junctions,
+            &config.branch_lines(),
+            self_crossings,
+            connectivity,
+        )
+    });
+    Ok((net, layout))
+}
+
+fn apply_clearance_if_required(
+    net: build_graph::Network,
+    conf: &Option<station_clearance::StationClearanceConfig>,
+) -> Result<build_graph::Network, TrialFailure> {
+    match conf {
+        Some(conf) => station_clearance::apply_clearance(net, conf),
+        None => Ok(net),
+    }
+}
+
+fn apply_robustness_if_required(
+    net: build_graph::Network,
+    req: Option<robustness::Robustness>,
+) -> Result<build_graph::Network, TrialFailure> {
+    match req {
+        Some(req) => robustness::apply_robustness(net, req),
+        None => Ok(net),
+    }
+}
+
+/// The layout is built only when it is saved.
+type NetworkWithLayout = (build_graph::Network, Option<network_layout::Layout>);
+
+fn build_network(config: &Configuration) -> (Option<NetworkWithLayout>, TrialStatistics) {
+    let factory_config = config.make_factory_config();
+    let mut bezier_points_factory = bezier_point_factory::BezierPointFactory::new(&factory_config);
+    let mut trials = TrialCounter::new(config.trials);
+    let mut stats = TrialStatistics::default();
+    let mut output = None;
     while trials.run() {
-        if let Some(output) = try_build_network(&mut bezier_points_factory, lines) {
-            return Some(output);
+        stats.new_trial();
+        match try_build_network(&mut bezier_points_factory, config, &mut stats) {
+            Ok(result) => {
+                output = Some(result);
+                break;
+            }
+            Err(failure) => stats.add_failure(failure),
         }
         bezier_points_factory.reset();
     }
-    None
+    stats.sampling = bezier_points_factory.statistics();
+    (output, stats)
 }
 
 fn load_config(f: PathBuf) -> MResult<Configuration> {
     let file = File::open(f)?;
-    let conf = serde_yaml::from_reader(file)?;
+    let conf: Configuration = serde_yaml::from_reader(file)?;
+    conf.validate()?;
     Ok(conf)
 }
 
@@ -142,11 +328,7 @@ where
     Ok(())
 }
 
-fn save_if_required(
-    net: &build_graph::Network,
-    format: &Option<SaveFormat>,
-    id: usize,
-) -> MResult<()> {
+fn save_if_required<T: Serialize>(net: &T, format: &Option<SaveFormat>, id: usize) -> MResult<()> {
     if let Some(format) = format {
         match format {
             SaveFormat::SaveJson(name) => serialize(name, id, "json", net, serde_json::to_writer)?,
@@ -170,6 +352,32 @@ fn export_if_required(
     Ok(())
 }
 
+/// Save each growth stage as *base-id-stage-k*.
+fn save_growth_if_required(
+    net: &build_graph::Network,
+    config: &Configuration,
+    id: usize,
+) -> MResult<()> {
+    let (conf, format) = match (&config.growth, &config.save_option) {
+        (Some(conf), Some(format)) => (conf, format),
+        _ => return Ok(()),
+    };
+    let growth = growth::grow_network(net, conf)?;
+    if !growth.skipped.is_empty() {
+        println!(
+            "Instance {id}: disconnected growth stages skipped: {:?}",
+            growth.skipped
+        );
+    }
+    for mut stage in growth.stages {
+        stage.network.assign_arc_services();
+        stage.network = apply_all_direct_path_is_required(stage.network, &config.all_direct_path);
+        let format = Some(format.with_suffix(&format!("{id}-stage")));
+        save_if_required(&stage, &format, stage.stage)?;
+    }
+    Ok(())
+}
+
 fn apply_station_wait_if_required(
     net: build_graph::Network,
     conf: &Option<station_wait_times::StationWaitTimeConfig>,
@@ -180,6 +388,36 @@ fn apply_station_wait_if_required(
     }
 }
 
+fn apply_frequencies_if_required(
+    net: build_graph::Network,
+    conf: &Option<station_wait_times::FrequencyConfig>,
+) -> MResult<build_graph::Network> {
+    match conf {
+        Some(conf) => station_wait_times::add_line_frequencies(net, conf),
+        None => Ok(net),
+    }
+}
+
+fn apply_shared_services_if_required(
+    net: build_graph::Network,
+    count: Option<usize>,
+) -> build_graph::Network {
+    match count {
+        Some(count) => shared_services::add_shared_services(net, count),
+        None => net,
+    }
+}
+
+fn apply_express_if_required(
+    net: build_graph::Network,
+    conf: &Option<express_services::ExpressConfig>,
+) -> MResult<build_graph::Network> {
+    match conf {
+        Some(conf) => express_services::add_express_services(net, conf),
+        None => Ok(net),
+    }
+}
+
 fn apply_all_direct_path_is_required(
     net: build_graph::Network,
     conf: &Option<bool>,
@@ -192,12 +430,206 @@ fn apply_all_direct_path_is_required(
     }
 }
 
-fn build_random_instance(config: &Configuration, id: usize) -> MResult<()> {
-    let factory_config = config.make_factory_config();
+fn stats_if_required(
+    net: &build_graph::Network,
+    base_name: &Option<String>,
+    id: usize,
+) -> MResult<Option<stats::NetworkStats>> {
+    match base_name {
+        Some(base_name) => {
+            let stats = stats::network_stats(net);
+            serialize(base_name, id, "json", &stats, serde_json::to_writer)?;
+            Ok(Some(stats))
+        }
+        None => Ok(None),
+    }
+}
 
-    if let Some(network) = build_network(&factory_config, config.trials, &config.lines) {
+fn skim_if_required(
+    net: &build_graph::Network,
+    format: &Option<skim::SkimFormat>,
+    id: usize,
+) -> MResult<()> {
+    if let Some(format) = format {
+        let base = format.base_name();
+        let ext = format.extension();
+        let times = File::create(format!("{base}-{id}-time.{ext}"))?;
+        let transfers = File::create(format!("{base}-{id}-transfers.{ext}"))?;
+        skim::write_skim(
+            net,
+            format,
+            BufWriter::new(times),
+            BufWriter::new(transfers),
+        )?;
+    }
+    Ok(())
+}
+
+fn pesp_if_required(
+    net: &build_graph::Network,
+    conf: &Option<pesp::PespConfig>,
+    id: usize,
+) -> MResult<()> {
+    if let Some(conf) = conf {
+        let base = &conf.base_name;
+        let ean = pesp::event_activity_network(net, conf)?;
+        let file = |name: &str| File::create(format!("{base}-{id}-{name}.giv")).map(BufWriter::new);
+        pesp::write_stops(net, file("stops")?)?;
+        pesp::write_events(&ean, file("events")?)?;
+        pesp::write_activities(&ean, file("activities")?)?;
+    }
+    Ok(())
+}
+
+fn time_expanded_if_required(
+    net: &build_graph::Network,
+    conf: &Option<time_expanded::TimeExpandedConfig>,
+    id: usize,
+) -> MResult<()> {
+    if let Some(conf) = conf {
+        let base = &conf.base_name;
+        let graph = time_expanded::time_expanded_graph(net, conf)?;
+        serialize(base, id, "json", &graph, serde_json::to_writer)?;
+        let edges = File::create(mk_file_name(base, id, "edges"))?;
+        time_expanded::write_edge_list(&graph, BufWriter::new(edges))?;
+    }
+    Ok(())
+}
+
+fn line_planning_if_required(
+    net: &build_graph::Network,
+    conf: &Option<line_planning::LinePlanningConfig>,
+    id: usize,
+) -> MResult<()> {
+    if let Some(conf) = conf {
+        let instance = line_planning::line_planning_instance(net, conf);
+        serialize(
+            &conf.base_name,
+            id,
+            "json",
+            &instance,
+            serde_json::to_writer,
+        )?;
+    }
+    Ok(())
+}
+
+fn scenarios_if_required(
+    net: &build_graph::Network,
+    conf: &Option<scenarios::ScenarioConfig>,
+    id: usize,
+) -> MResult<()> {
+    if let Some(conf) = conf {
+        let scenarios = scenarios::weight_scenarios(net, conf)?;
+        serialize(
+            &conf.base_name,
+            id,
+            "json",
+            &scenarios,
+            serde_json::to_writer,
+        )?;
+    }
+    Ok(())
+}
+
+fn disruption_if_required(
+    net: &build_graph::Network,
+    conf: &Option<disruption::DisruptionConfig>,
+    id: usize,
+) -> MResult<()> {
+    if let Some(conf) = conf {
+        let disruptions = disruption::disruptions(net, conf, id);
+        serialize(
+            &conf.base_name,
+            id,
+            "json",
+            &disruptions,
+            serde_json::to_writer,
+        )?;
+    }
+    Ok(())
+}
+
+fn save_summary_if_required(
+    stats: &[stats::NetworkStats],
+    base_name: &Option<String>,
+) -> MResult<()> {
+    if let Some(base_name) = base_name {
+        let mut file = File::create(format!("{base_name}-summary.csv"))?;
+        write!(file, "{}", stats::aggregate_table(stats))?;
+    }
+    Ok(())
+}
+
+fn apply_centrality_if_required(
+    mut net: build_graph::Network,
+    conf: &Option<bool>,
+) -> build_graph::Network {
+    if conf.unwrap_or(false) {
+        net.centrality = Some(centrality::centrality(&net.graph));
+    }
+    net
+}
+
+fn apply_demand_if_required(
+    net: build_graph::Network,
+    conf: &Option<demand::DemandConfig>,
+) -> build_graph::Network {
+    match conf {
+        Some(conf) => demand::add_demand(net, conf),
+        None => net,
+    }
+}
+
+fn apply_assignment_if_required(
+    mut net: build_graph::Network,
+    conf: &Option<assignment::AssignmentConfig>,
+) -> MResult<build_graph::Network> {
+    let conf = match conf {
+        Some(conf) => conf,
+        None => return Ok(net),
+    };
+    if let Some(capacity) = &conf.capacity {
+        assignment::assign_capacities(&mut net, capacity)?;
+    }
+    if let Some(file) = &conf.demand_file {
+        net.demand = assignment::read_demand_csv(File::open(file)?, net.points.len())?;
+    }
+    Ok(assignment::assign_flows(net, conf.method))
+}
+
+fn build_random_instance(
+    config: &Configuration,
+    id: usize,
+) -> MResult<Option<stats::NetworkStats>> {
+    let mut output = None;
+    let (network, stats) = build_network(config);
+    println!("Instance {id}: {stats}");
+    if let Some((network, layout)) = network {
+        if let Some(layout) = layout {
+            let format = config.save_option.as_ref().map(|f| f.with_suffix("layout"));
+            save_if_required(&layout, &format, id)?;
+        }
+        if let Some(targets) = &config.targets {
+            println!("Instance {id}: {}", targets.report(&network));
+        }
         let network = apply_station_wait_if_required(network, &config.station_wait)?;
+        save_growth_if_required(&network, config, id)?;
+        let network = apply_shared_services_if_required(network, config.shared_services);
+        let network = apply_express_if_required(network, &config.express)?;
+        let mut network = apply_frequencies_if_required(network, &config.frequencies)?;
+        network.assign_arc_services();
+        output = stats_if_required(&network, &config.stats, id)?;
+        skim_if_required(&network, &config.skim, id)?;
+        pesp_if_required(&network, &config.pesp, id)?;
+        time_expanded_if_required(&network, &config.time_expanded, id)?;
+        scenarios_if_required(&network, &config.scenarios, id)?;
+        disruption_if_required(&network, &config.disruption, id)?;
         let network = apply_all_direct_path_is_required(network, &config.all_direct_path);
+        let network = apply_centrality_if_required(network, &config.centrality);
+        let network = apply_demand_if_required(network, &config.demand);
+        let network = apply_assignment_if_required(network, &config.assignment)?;
+        line_planning_if_required(&network, &config.line_planning, id)?;
         save_if_required(&network, &config.save_option, id)?;
         export_if_required(&network, &config.export_graph, id)?;
     } else {
@@ -207,16 +639,75 @@ fn build_random_instance(config: &Configuration, id: usize) -> MResult<()> {
         );
     }
 
+    Ok(output)
+}
+
+#[derive(Deserialize)]
+struct EditConfiguration {
+    edits: Vec<line_edit::LineEdit>,
+    save_option: Option<SaveFormat>,
+    export_graph: Option<String>,
+}
+
+/// Load a saved layout or network, as JSON or YAML according to its extension.
+fn load_saved<T: DeserializeOwned>(f: PathBuf) -> MResult<T> {
+    let is_json = f.extension().is_some_and(|ext| ext == "json");
+    let file = File::open(f)?;
+    let output = if is_json {
+        serde_json::from_reader(file)?
+    } else {
+        serde_yaml::from_reader(file)?
+    };
+    Ok(output)
+}
+
+/// Apply the edits to the layout and rebuild the network: the
+/// edited layout is saved too, so that edits can be chained.
+fn edit_layout(layout: PathBuf, edits: PathBuf) -> MResult<()> {
+    let mut layout: network_layout::Layout = load_saved(layout)?;
+    let config: EditConfiguration = serde_yaml::from_reader(File::open(edits)?)?;
+    for edit in &config.edits {
+        line_edit::apply_edit(&mut layout, edit)?;
+    }
+    let network = layout.build_network()?;
+    save_if_required(&network, &config.save_option, 0)?;
+    let format = config.save_option.as_ref().map(|f| f.with_suffix("layout"));
+    save_if_required(&layout, &format, 0)?;
+    export_if_required(&network, &config.export_graph, 0)?;
+    Ok(())
+}
+
+fn route(
+    network: PathBuf,
+    origin: usize,
+    destination: usize,
+    criteria: routing::Criteria,
+) -> MResult<()> {
+    let network: build_graph::Network = load_saved(network)?;
+    let journeys = routing::plan(&network, origin, destination, criteria)?;
+    println!("{}", serde_json::to_string_pretty(&journeys)?);
     Ok(())
 }
 
 fn main() -> MResult<()> {
     let args = Arguments::from_args();
-    let config = load_config(args.file)?;
-
+    let file = match args.command {
+        Some(Command::Edit { layout, edits }) => return edit_layout(layout, edits),
+        Some(Command::Route {
+            network,
+            origin,
+            destination,
+            criteria,
+        }) => return route(network, origin, destination, criteria),
+        None => args.file.ok_or("missing configuration file")?,
+    };
+    let config = load_config(file)?;
+
+    let mut stats = vec![];
     for i in 0..config.count {
-        build_random_instance(&config, i)?;
+        stats.extend(build_random_instance(&config, i)?);
     }
+    save_summary_if_required(&stats, &config.stats)?;
 
     Ok(())
 }
@@ -1,13 +1,136 @@
 use crate::bezier_point_factory;
+use crate::intersections::Junction;
+use crate::rand_utils::random_in_range;
+use crate::self_intersections::{self, SelfIntersectionPolicy};
+use crate::trial_statistics::TrialFailure;
 use crate::Curve;
-use flo_curves::{bezier, BezierCurveFactory};
+use flo_curves::{bezier, BezierCurve, BezierCurveFactory, Coord2, Coordinate};
+use serde::Deserialize;
 
-pub fn make_curves(pf: &mut bezier_point_factory::BezierPointFactory, count: usize) -> Vec<Curve> {
-    (0..count).map(|_| make_curve(pf)).collect()
+const MAX_RESAMPLE: usize = 100;
+const JUNCTION_RANGE: (f64, f64) = (0.2, 0.8);
+
+#[derive(Deserialize)]
+pub struct BranchConfig {
+    pub line: usize,
+    pub stations: usize,
+}
+
+pub fn make_curves(
+    pf: &mut bezier_point_factory::BezierPointFactory,
+    count: usize,
+    policy: Option<SelfIntersectionPolicy>,
+) -> Result<Vec<Curve>, TrialFailure> {
+    (0..count).map(|_| make_curve(pf, policy)).collect()
+}
+
+/// Append to *curves* one curve for each branch. Each branch
+/// starts from a new terminal and ends on a point of its trunk.
+pub fn make_branch_curves(
+    pf: &mut bezier_point_factory::BezierPointFactory,
+    curves: &mut Vec<Curve>,
+    branches: &[BranchConfig],
+    policy: Option<SelfIntersectionPolicy>,
+) -> Result<Vec<Junction>, TrialFailure> {
+    let mut junctions = Vec::with_capacity(branches.len());
+    for branch in branches {
+        let position = random_in_range(JUNCTION_RANGE);
+        junctions.push(Junction {
+            trunk: branch.line,
+            branch: curves.len(),
+            position,
+        });
+        curves.push(make_branch(pf, &curves[branch.line], position, policy)?);
+    }
+    Ok(junctions)
+}
+
+/// Replace the given curves with new random ones. A branch keeps
+/// its junction, a trunk moves the junctions of its branches.
+pub fn resample_curves(
+    pf: &mut bezier_point_factory::BezierPointFactory,
+    curves: &mut [Curve],
+    junctions: &mut [Junction],
+    lines: &[usize],
+    policy: Option<SelfIntersectionPolicy>,
+) -> Result<(), TrialFailure> {
+    for line in lines {
+        if junctions.iter().all(|j| j.branch != *line) {
+            pf.release_terminal(terminal(curves[*line].start_point()));
+            pf.release_terminal(terminal(curves[*line].end_point()));
+            curves[*line] = make_curve(pf, policy)?;
+        }
+        for junction in junctions.iter_mut() {
+            if junction.trunk == *line {
+                junction.position = random_in_range(JUNCTION_RANGE);
+            }
+            if junction.trunk == *line || junction.branch == *line {
+                // the other end of a branch lies on its trunk
+                pf.release_terminal(terminal(curves[junction.branch].start_point()));
+                curves[junction.branch] =
+                    make_branch(pf, &curves[junction.trunk], junction.position, policy)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn terminal(pt: Coord2) -> bezier_point_factory::Point {
+    (pt.get(0), pt.get(1))
+}
+
+fn make_branch(
+    pf: &mut bezier_point_factory::BezierPointFactory,
+    trunk: &Curve,
+    position: f64,
+    policy: Option<SelfIntersectionPolicy>,
+) -> Result<Curve, TrialFailure> {
+    let end = trunk.point_at_pos(position);
+    let points = pf
+        .get_branch_points((end.get(0), end.get(1)))
+        .map_err(|_| TrialFailure::Sampling)?;
+    check_curve(pf, points, policy)
+}
+
+fn make_curve(
+    point_factory: &mut bezier_point_factory::BezierPointFactory,
+    policy: Option<SelfIntersectionPolicy>,
+) -> Result<Curve, TrialFailure> {
+    let points = point_factory
+        .get_bezier_points()
+        .map_err(|_| TrialFailure::Sampling)?;
+    check_curve(point_factory, points, policy)
+}
+
+fn check_curve(
+    point_factory: &bezier_point_factory::BezierPointFactory,
+    mut points: bezier_point_factory::BezierPoints,
+    policy: Option<SelfIntersectionPolicy>,
+) -> Result<Curve, TrialFailure> {
+    let mut curve = points_to_curve(&points);
+    match policy {
+        Some(SelfIntersectionPolicy::Reject) if has_defect(&curve) => {
+            Err(TrialFailure::SelfIntersection)
+        }
+        Some(SelfIntersectionPolicy::Resample) => {
+            for _ in 0..MAX_RESAMPLE {
+                if !has_defect(&curve) {
+                    return Ok(curve);
+                }
+                points = point_factory.resample_control_points(points);
+                curve = points_to_curve(&points);
+            }
+            Err(TrialFailure::SelfIntersection)
+        }
+        _ => Ok(curve),
+    }
+}
+
+fn has_defect(curve: &Curve) -> bool {
+    self_intersections::find_defect(curve).is_some()
 }
 
-fn make_curve(point_factory: &mut bezier_point_factory::BezierPointFactory) -> Curve {
-    let points = point_factory.get_bezier_points();
+fn points_to_curve(points: &bezier_point_factory::BezierPoints) -> Curve {
     bezier::Curve::from_points(
         points.start.into(),
         (points.ctrl_1.into(), points.ctrl_2.into()),
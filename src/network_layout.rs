@@ -0,0 +1,123 @@
+use crate::build_graph::{self, Network, Pt};
+use crate::connectivity::{self, ConnectivityPolicy};
+use crate::intersections::{self, Junction};
+use crate::trial_statistics::TrialFailure;
+use crate::Curve;
+use flo_curves::{bezier, BezierCurve, BezierCurveFactory, Coordinate};
+use serde::{Deserialize, Serialize};
+
+/// Geometry of a generated network: enough to
+/// rebuild it after its curves have been edited.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Layout {
+    /// Start, control points and end of each curve:
+    /// lines first, then branches.
+    pub curves: Vec<[Pt; 4]>,
+    /// Position, on each curve, of the stations that
+    /// are not crossings with other curves.
+    pub stations: Vec<Vec<f64>>,
+    pub junctions: Vec<Junction>,
+    pub branch_lines: Vec<usize>,
+    pub self_crossings: bool,
+    #[serde(default)]
+    pub connectivity: ConnectivityPolicy,
+}
+
+impl Layout {
+    /// *nodes* are the station positions, *intersections*
+    /// the crossings found on each curve.
+    pub fn new(
+        curves: &[Curve],
+        nodes: &[Vec<f64>],
+        intersections: &[Vec<f64>],
+        junctions: &[Junction],
+        branch_lines: &[usize],
+        self_crossings: bool,
+        connectivity: ConnectivityPolicy,
+    ) -> Self {
+        let stations = nodes
+            .iter()
+            .zip(intersections)
+            .map(|(n, i)| n.iter().copied().filter(|t| !i.contains(t)).collect())
+            .collect();
+        Self {
+            curves: curves.iter().map(curve_points).collect(),
+            stations,
+            junctions: junctions.to_vec(),
+            branch_lines: branch_lines.to_vec(),
+            self_crossings,
+            connectivity,
+        }
+    }
+
+    pub fn curves(&self) -> Vec<Curve> {
+        self.curves.iter().map(points_curve).collect()
+    }
+
+    /// Find again the intersections between the
+    /// curves and build the resulting network.
+    pub fn build_network(&self) -> Result<Network, TrialFailure> {
+        let curves = self.curves();
+        let inter = intersections::make_intersection_lists(
+            &curves,
+            &self.junctions,
+            None,
+            self.self_crossings,
+            self.connectivity.allow_disconnected(),
+        )?;
+        let nodes = merge_nodes(&self.stations, &inter.direct_intersections);
+        let net = build_graph::build_network(
+            &curves,
+            &nodes,
+            &inter.inverse_intersections,
+            &self.branch_lines,
+            self.connectivity.allow_disconnected(),
+        )?;
+        Ok(connectivity::apply_connectivity(net, self.connectivity))
+    }
+}
+
+/// The curve start (t = 0) is not a station
+/// unless it is explicitly listed in *stations*.
+fn merge_nodes(stations: &[Vec<f64>], intersections: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    stations
+        .iter()
+        .zip(intersections)
+        .map(|(s, i)| {
+            let inter = i.iter().filter(|t| **t != 0.);
+            let mut nodes: Vec<f64> = s.iter().chain(inter).copied().collect();
+            nodes.sort_by(f64::total_cmp);
+            nodes.dedup();
+            nodes
+        })
+        .collect()
+}
+
+fn curve_points(c: &Curve) -> [Pt; 4] {
+    let (c1, c2) = c.control_points();
+    [c.start_point(), c1, c2, c.end_point()].map(|p| (p.get(0), p.get(1)))
+}
+
+fn points_curve(p: &[Pt; 4]) -> Curve {
+    bezier::Curve::from_points(p[0].into(), (p[1].into(), p[2].into()), p[3].into())
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_merge_nodes() {
+        let stations = vec![vec![0.2, 0.7], vec![0., 0.5]];
+        let inter = vec![vec![0., 0.4, 1.], vec![0., 0.3, 1.]];
+        let nodes = merge_nodes(&stations, &inter);
+        assert_eq!(nodes, vec![vec![0.2, 0.4, 0.7, 1.], vec![0., 0.3, 0.5, 1.]]);
+    }
+
+    #[test]
+    fn test_curve_points() {
+        let points = [(0., 0.), (1., 2.), (3., 2.), (4., 0.)];
+        assert_eq!(curve_points(&points_curve(&points)), points);
+    }
+}
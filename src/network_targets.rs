@@ -0,0 +1,150 @@
+use crate::build_graph::Network;
+use crate::trial_statistics::TrialFailure;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Deserialize)]
+pub struct NetworkTargets {
+    pub min_interchanges: Option<usize>,
+    pub max_interchanges: Option<usize>,
+    pub max_station_lines: Option<usize>,
+    #[serde(default)]
+    pub single_crossing: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TargetValues {
+    pub interchanges: usize,
+    pub max_station_lines: usize,
+    pub max_pair_crossings: usize,
+}
+
+impl NetworkTargets {
+    pub fn check(&self, net: &Network) -> Result<(), TrialFailure> {
+        if self.accept(&measure(&net.station_lines())) {
+            Ok(())
+        } else {
+            Err(TrialFailure::Targets)
+        }
+    }
+
+    fn accept(&self, values: &TargetValues) -> bool {
+        let min_inter = self.min_interchanges.unwrap_or(0);
+        let max_inter = self.max_interchanges.unwrap_or(usize::MAX);
+        let max_lines = self.max_station_lines.unwrap_or(usize::MAX);
+        let max_crossings = if self.single_crossing { 1 } else { usize::MAX };
+        (min_inter..=max_inter).contains(&values.interchanges)
+            && values.max_station_lines <= max_lines
+            && values.max_pair_crossings <= max_crossings
+    }
+
+    pub fn report<'a>(&'a self, net: &Network) -> TargetReport<'a> {
+        TargetReport {
+            targets: self,
+            values: measure(&net.station_lines()),
+        }
+    }
+}
+
+pub struct TargetReport<'a> {
+    targets: &'a NetworkTargets,
+    values: TargetValues,
+}
+
+impl<'a> fmt::Display for TargetReport<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let targets = self.targets;
+        let values = &self.values;
+        write!(f, "interchanges: {}", values.interchanges)?;
+        match (targets.min_interchanges, targets.max_interchanges) {
+            (Some(min), Some(max)) => write!(f, " (requested {min}..={max})")?,
+            (Some(min), None) => write!(f, " (requested >= {min})")?,
+            (None, Some(max)) => write!(f, " (requested <= {max})")?,
+            (None, None) => {}
+        }
+        write!(f, ", max lines per station: {}", values.max_station_lines)?;
+        if let Some(max) = targets.max_station_lines {
+            write!(f, " (requested <= {max})")?;
+        }
+        write!(
+            f,
+            ", max crossings per line pair: {}",
+            values.max_pair_crossings
+        )?;
+        if targets.single_crossing {
+            write!(f, " (requested <= 1)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Measure the network from the lines serving each station.
+pub fn measure(station_lines: &[Vec<usize>]) -> TargetValues {
+    let interchanges = station_lines.iter().filter(|l| l.len() > 1).count();
+    let max_station_lines = station_lines.iter().map(|l| l.len()).max().unwrap_or(0);
+
+    let mut crossings: HashMap<(usize, usize), usize> = HashMap::new();
+    for l in station_lines {
+        for (k, i) in l.iter().enumerate() {
+            for j in &l[k + 1..] {
+                *crossings.entry((*i, *j)).or_default() += 1;
+            }
+        }
+    }
+    let max_pair_crossings = crossings.values().copied().max().unwrap_or(0);
+
+    TargetValues {
+        interchanges,
+        max_station_lines,
+        max_pair_crossings,
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_measure() {
+        let station_lines = vec![
+            vec![0],
+            vec![0],
+            vec![0, 1, 2],
+            vec![0],
+            vec![0, 1],
+            vec![1, 2],
+            vec![2, 3],
+            vec![3],
+        ];
+        let values = measure(&station_lines);
+        let expected = TargetValues {
+            interchanges: 4,
+            max_station_lines: 3,
+            max_pair_crossings: 2,
+        };
+        assert_eq!(values, expected);
+    }
+
+    #[test]
+    fn test_accept() {
+        let targets = NetworkTargets {
+            min_interchanges: Some(3),
+            max_interchanges: None,
+            max_station_lines: Some(3),
+            single_crossing: true,
+        };
+        let mut values = TargetValues {
+            interchanges: 4,
+            max_station_lines: 3,
+            max_pair_crossings: 1,
+        };
+        assert!(targets.accept(&values));
+        values.max_pair_crossings = 2;
+        assert!(!targets.accept(&values));
+        values.max_pair_crossings = 1;
+        values.interchanges = 2;
+        assert!(!targets.accept(&values));
+    }
+}
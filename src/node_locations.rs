@@ -6,8 +6,10 @@ pub fn generate_node_lists(inters: Vec<Vec<f64>>, counts: &[usize]) -> Vec<Vec<f
         .collect()
 }
 
+/// Subdivide the intervals between the intersections: a curve
+/// with more intersections than *count* gets no other station.
 fn generate_node(inter: Vec<f64>, count: usize) -> Vec<f64> {
-    let rem = count - inter.len();
+    let rem = count.saturating_sub(inter.len());
     let mut output = Vec::with_capacity(count);
     let rem = rem as f64;
     let mut prev = None;
@@ -71,6 +73,12 @@ mod test {
         assert_eq!(result, expected);
     }
 
+    #[test]
+    fn test_more_intersections_than_stations() {
+        let result = generate_node(vec![0., 0.5, 1.], 2);
+        assert_eq!(result, vec![0.5, 1.]);
+    }
+
     fn subdivide(interval: (f64, f64), count: f64) -> Vec<f64> {
         let subdivisions = SubdivisionIterator::new(interval, count);
         subdivisions.collect()
@@ -0,0 +1,386 @@
+use crate::build_graph::{Network, TransferKind};
+use crate::shortest_path;
+use crate::MResult;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+#[derive(Deserialize)]
+pub struct PespConfig {
+    pub base_name: String,
+    pub period: f64,
+    /// Shortest time to change service, walking excluded.
+    #[serde(default)]
+    pub min_change: f64,
+    /// Shortest time between two departures on the same track.
+    #[serde(default)]
+    pub min_headway: f64,
+    /// Extra time allowed on top of the travel time, as a fraction of it.
+    #[serde(default)]
+    pub drive_slack: f64,
+    /// Time spent by a vehicle at each intermediate station.
+    #[serde(default)]
+    pub dwell: f64,
+    /// Extra time allowed on top of the dwell time.
+    #[serde(default)]
+    pub dwell_slack: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EventKind {
+    Arrival,
+    Departure,
+}
+
+/// Arrival or departure of a service pattern at a station. Patterns
+/// run in both directions; a line with frequency f runs f * period
+/// times in each period, numbered by `repetition`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Event {
+    pub kind: EventKind,
+    pub station: usize,
+    pub pattern: usize,
+    pub forward: bool,
+    pub repetition: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ActivityKind {
+    Drive,
+    Dwell,
+    Change,
+    Headway,
+    Sync,
+}
+
+impl ActivityKind {
+    /// Name of the activity type in LinTim files.
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Drive => "drive",
+            Self::Dwell => "wait",
+            Self::Change => "change",
+            Self::Headway => "headway",
+            Self::Sync => "sync",
+        }
+    }
+}
+
+/// Periodic constraint: the time from event `from` to
+/// event `to`, modulo the period, is in [lower, upper].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Activity {
+    pub kind: ActivityKind,
+    pub from: usize,
+    pub to: usize,
+    pub lower: f64,
+    pub upper: f64,
+}
+
+#[derive(Debug, Default)]
+pub struct EventActivityNetwork {
+    pub events: Vec<Event>,
+    pub activities: Vec<Activity>,
+}
+
+impl EventActivityNetwork {
+    fn add_event(&mut self, event: Event) -> usize {
+        self.events.push(event);
+        self.events.len() - 1
+    }
+
+    fn add_activity(&mut self, kind: ActivityKind, from: usize, to: usize, bounds: (f64, f64)) {
+        let (lower, upper) = bounds;
+        self.activities.push(Activity {
+            kind,
+            from,
+            to,
+            lower,
+            upper,
+        });
+    }
+}
+
+/// Build the periodic event-activity network of *net*: driving times
+/// are the arc weights, dwell times come from *conf*. Changes
+/// connect the arrivals and departures of different patterns at the
+/// same station or at the two ends of a walking transfer. Fails when
+/// two consecutive stations of a pattern are not connected by an arc.
+pub fn event_activity_network(net: &Network, conf: &PespConfig) -> MResult<EventActivityNetwork> {
+    let mut ean = EventActivityNetwork::default();
+    let mut arrivals: Vec<Vec<usize>> = vec![vec![]; net.points.len()];
+    let mut departures: Vec<Vec<usize>> = vec![vec![]; net.points.len()];
+    let mut sections: BTreeMap<(usize, usize), Vec<usize>> = BTreeMap::new();
+
+    for (p, pattern) in net.lines.iter().enumerate() {
+        let repetitions = repetitions(net, p, conf.period);
+        for forward in [true, false] {
+            let stations: Vec<usize> = if forward {
+                pattern.clone()
+            } else {
+                pattern.iter().rev().copied().collect()
+            };
+            let mut first_departures = vec![];
+            for repetition in 0..repetitions {
+                let event = |kind, station| Event {
+                    kind,
+                    station,
+                    pattern: p,
+                    forward,
+                    repetition,
+                };
+                let mut prev_departure = None;
+                for (k, s) in stations.iter().enumerate() {
+                    let mut arrival = None;
+                    if let Some((dep, from)) = prev_departure {
+                        let time = shortest_path::arc_weight(&net.graph, from, *s)
+                            .ok_or_else(|| format!("pattern {p} has no arc from {from} to {s}"))?;
+                        let arr = ean.add_event(event(EventKind::Arrival, *s));
+                        arrivals[*s].push(arr);
+                        let bounds = (time, time * (1. + conf.drive_slack));
+                        ean.add_activity(ActivityKind::Drive, dep, arr, bounds);
+                        arrival = Some(arr);
+                    }
+                    if k + 1 == stations.len() {
+                        break;
+                    }
+                    let dep = ean.add_event(event(EventKind::Departure, *s));
+                    departures[*s].push(dep);
+                    sections.entry((*s, stations[k + 1])).or_default().push(dep);
+                    match arrival {
+                        Some(arr) => {
+                            let bounds = (conf.dwell, conf.dwell + conf.dwell_slack);
+                            ean.add_activity(ActivityKind::Dwell, arr, dep, bounds);
+                        }
+                        None => first_departures.push(dep),
+                    }
+                    prev_departure = Some((dep, *s));
+                }
+            }
+            // repetitions of the same pattern are evenly spaced
+            let gap = conf.period / repetitions as f64;
+            for pair in first_departures.windows(2) {
+                ean.add_activity(ActivityKind::Sync, pair[0], pair[1], (gap, gap));
+            }
+        }
+    }
+
+    let change = |walk: f64| (conf.min_change + walk, conf.min_change + walk + conf.period);
+    for s in 0..net.points.len() {
+        add_changes(&mut ean, &arrivals[s], &departures[s], change(0.));
+    }
+    for t in net
+        .transfers
+        .iter()
+        .filter(|t| t.kind == TransferKind::Walking)
+    {
+        let (i, j) = t.stations;
+        add_changes(&mut ean, &arrivals[i], &departures[j], change(t.distance));
+        add_changes(&mut ean, &arrivals[j], &departures[i], change(t.distance));
+    }
+
+    let headway = (conf.min_headway, conf.period - conf.min_headway);
+    for deps in sections.values() {
+        for a in deps {
+            for b in deps {
+                if ean.events[*a].pattern != ean.events[*b].pattern {
+                    ean.add_activity(ActivityKind::Headway, *a, *b, headway);
+                }
+            }
+        }
+    }
+    Ok(ean)
+}
+
+fn add_changes(
+    ean: &mut EventActivityNetwork,
+    arrivals: &[usize],
+    departures: &[usize],
+    bounds: (f64, f64),
+) {
+    for arr in arrivals {
+        for dep in departures {
+            if ean.events[*arr].pattern != ean.events[*dep].pattern {
+                ean.add_activity(ActivityKind::Change, *arr, *dep, bounds);
+            }
+        }
+    }
+}
+
+/// Runs of *pattern* in a period, one when line frequencies are unknown.
+fn repetitions(net: &Network, pattern: usize, period: f64) -> usize {
+    let line = net.line_ids[pattern];
+    net.line_frequencies
+        .get(line)
+        .map_or(1, |f| (f.frequency * period).round().max(1.) as usize)
+}
+
+/// Write the stops in the LinTim `Stop.giv` format.
+pub fn write_stops<W: Write>(net: &Network, mut output: W) -> io::Result<()> {
+    writeln!(
+        output,
+        "# stop-id; short-name; long-name; x-coordinate; y-coordinate"
+    )?;
+    for (k, (x, y)) in net.points.iter().enumerate() {
+        writeln!(output, "{}; \"{k}\"; \"station {k}\"; {x}; {y}", k + 1)?;
+    }
+    output.flush()
+}
+
+/// Write the events in the LinTim `Events-periodic.giv` format.
+/// Stations and patterns are numbered from one.
+pub fn write_events<W: Write>(ean: &EventActivityNetwork, mut output: W) -> io::Result<()> {
+    writeln!(
+        output,
+        "# event-id; type; stop-id; line-id; passengers; line-direction; line-freq-repetition"
+    )?;
+    for (k, e) in ean.events.iter().enumerate() {
+        let kind = match e.kind {
+            EventKind::Arrival => "arrival",
+            EventKind::Departure => "departure",
+        };
+        let direction = if e.forward { ">" } else { "<" };
+        writeln!(
+            output,
+            "{}; \"{kind}\"; {}; {}; 0; {direction}; {}",
+            k + 1,
+            e.station + 1,
+            e.pattern + 1,
+            e.repetition + 1
+        )?;
+    }
+    output.flush()
+}
+
+/// Write the activities in the LinTim `Activities-periodic.giv` format.
+pub fn write_activities<W: Write>(ean: &EventActivityNetwork, mut output: W) -> io::Result<()> {
+    writeln!(
+        output,
+        "# activity-index; type; from-event; to-event; lower-bound; upper-bound; passengers"
+    )?;
+    for (k, a) in ean.activities.iter().enumerate() {
+        writeln!(
+            output,
+            "{}; \"{}\"; {}; {}; {}; {}; 0",
+            k + 1,
+            a.kind.name(),
+            a.from + 1,
+            a.to + 1,
+            a.lower,
+            a.upper
+        )?;
+    }
+    output.flush()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::build_graph::{test_network, LineFrequency};
+    use simplegraph::Graph;
+
+    #[test]
+    fn test_event_activity_network() {
+        // line 0: 0 - 1 - 2, line 1: 3 - 1 - 2
+        let mut net = make_network();
+        let conf = make_config();
+        let ean = event_activity_network(&net, &conf).unwrap();
+        // 4 events for each line and direction
+        assert_eq!(ean.events.len(), 16);
+        assert_eq!(count_kind(&ean, ActivityKind::Drive), 8);
+        assert_eq!(count_kind(&ean, ActivityKind::Dwell), 4);
+        // 4 arrivals and 4 departures at station 1,
+        // terminal arrivals and departures at station 2
+        assert_eq!(count_kind(&ean, ActivityKind::Change), 8 + 2);
+        // both lines run on 1 - 2, in both directions
+        assert_eq!(count_kind(&ean, ActivityKind::Headway), 4);
+
+        let drive = ean
+            .activities
+            .iter()
+            .find(|a| a.kind == ActivityKind::Drive)
+            .unwrap();
+        assert_eq!((drive.lower, drive.upper), (2., 2.5));
+        let dwell = ean
+            .activities
+            .iter()
+            .find(|a| a.kind == ActivityKind::Dwell)
+            .unwrap();
+        assert_eq!((dwell.lower, dwell.upper), (0.5, 1.5));
+
+        net.line_frequencies = vec![
+            LineFrequency {
+                frequency: 0.2,
+                boarding_wait: 2.5,
+            },
+            LineFrequency {
+                frequency: 0.1,
+                boarding_wait: 5.,
+            },
+        ];
+        let ean = event_activity_network(&net, &conf).unwrap();
+        assert_eq!(ean.events.len(), 2 * 8 + 8);
+        let sync: Vec<&Activity> = ean
+            .activities
+            .iter()
+            .filter(|a| a.kind == ActivityKind::Sync)
+            .collect();
+        assert_eq!(sync.len(), 2);
+        assert_eq!(sync[0].lower, 5.);
+        // runs of the same line are kept apart by the sync activities
+        assert_eq!(count_kind(&ean, ActivityKind::Headway), 8);
+    }
+
+    #[test]
+    fn test_missing_arc() {
+        let mut net = make_network();
+        net.lines.push(vec![0, 2]);
+        net.line_ids.push(2);
+        assert!(event_activity_network(&net, &make_config()).is_err());
+    }
+
+    #[test]
+    fn test_write_files() {
+        let net = make_network();
+        let ean = event_activity_network(&net, &make_config()).unwrap();
+        let mut events = vec![];
+        write_events(&ean, &mut events).unwrap();
+        let events = String::from_utf8(events).unwrap();
+        assert_eq!(
+            events.lines().nth(1),
+            Some("1; \"departure\"; 1; 1; 0; >; 1")
+        );
+        let mut activities = vec![];
+        write_activities(&ean, &mut activities).unwrap();
+        let activities = String::from_utf8(activities).unwrap();
+        assert_eq!(
+            activities.lines().nth(1),
+            Some("1; \"drive\"; 1; 2; 2; 2.5; 0")
+        );
+    }
+
+    fn count_kind(ean: &EventActivityNetwork, kind: ActivityKind) -> usize {
+        ean.activities.iter().filter(|a| a.kind == kind).count()
+    }
+
+    fn make_config() -> PespConfig {
+        PespConfig {
+            base_name: String::new(),
+            period: 10.,
+            min_change: 1.,
+            min_headway: 1.,
+            drive_slack: 0.25,
+            dwell: 0.5,
+            dwell_slack: 1.,
+        }
+    }
+
+    fn make_network() -> Network {
+        let lines = vec![vec![0, 1, 2], vec![3, 1, 2]];
+        let mut net = test_network(4, lines, &[(0, 1, 2.), (1, 2, 2.), (3, 1, 1.)]);
+        // boarding waits are not dwell times
+        net.graph.update_all_nodes_weight(|_, _| 3.);
+        net
+    }
+}
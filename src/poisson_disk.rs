@@ -0,0 +1,171 @@
+use crate::bezier_point_factory::Point;
+use crate::rand_utils::random_in_range;
+use std::collections::HashMap;
+
+const CANDIDATES_PER_POINT: usize = 30;
+
+pub struct PoissonDiskSamples {
+    pub points: Vec<Point>,
+    pub rejected: usize,
+}
+
+pub fn poisson_disk_samples(
+    x_range: (f64, f64),
+    y_range: (f64, f64),
+    radius: f64,
+    max_attempts: usize,
+) -> PoissonDiskSamples {
+    let mut sampler = BridsonSampler::new(x_range, y_range, radius);
+    if radius > 0. {
+        sampler.run(max_attempts);
+    }
+    let mut points = sampler.points;
+    fastrand::shuffle(&mut points);
+    PoissonDiskSamples {
+        points,
+        rejected: sampler.rejected,
+    }
+}
+
+struct BridsonSampler {
+    x_range: (f64, f64),
+    y_range: (f64, f64),
+    radius: f64,
+    grid: BackgroundGrid,
+    points: Vec<Point>,
+    active: Vec<usize>,
+    rejected: usize,
+}
+
+impl BridsonSampler {
+    fn new(x_range: (f64, f64), y_range: (f64, f64), radius: f64) -> Self {
+        let grid = BackgroundGrid::new(radius);
+        Self {
+            x_range,
+            y_range,
+            radius,
+            grid,
+            points: vec![],
+            active: vec![],
+            rejected: 0,
+        }
+    }
+
+    fn run(&mut self, max_attempts: usize) {
+        let mut attempts = 1;
+        let seed = (random_in_range(self.x_range), random_in_range(self.y_range));
+        self.insert(seed);
+
+        while !self.active.is_empty() && attempts < max_attempts {
+            let idx = fastrand::usize(..self.active.len());
+            let center = self.points[self.active[idx]];
+            let mut found = false;
+            for _ in 0..CANDIDATES_PER_POINT {
+                attempts += 1;
+                let pt = annulus_point(center, self.radius);
+                if self.valid_point(pt) {
+                    self.insert(pt);
+                    found = true;
+                    break;
+                }
+                self.rejected += 1;
+            }
+            if !found {
+                self.active.swap_remove(idx);
+            }
+        }
+    }
+
+    fn insert(&mut self, pt: Point) {
+        let idx = self.points.len();
+        self.points.push(pt);
+        self.active.push(idx);
+        self.grid.insert(pt, idx);
+    }
+
+    fn valid_point(&self, pt: Point) -> bool {
+        in_range(pt.0, self.x_range)
+            && in_range(pt.1, self.y_range)
+            && self
+                .grid
+                .neighbors(pt)
+                .all(|i| squared_distance(pt, self.points[i]) >= self.radius * self.radius)
+    }
+}
+
+struct BackgroundGrid {
+    cell_size: f64,
+    cells: HashMap<(i64, i64), usize>,
+}
+
+impl BackgroundGrid {
+    fn new(radius: f64) -> Self {
+        let cell_size = radius / std::f64::consts::SQRT_2;
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+        }
+    }
+
+    fn cell(&self, pt: Point) -> (i64, i64) {
+        let (x, y) = pt;
+        let col = (x / self.cell_size).floor() as i64;
+        let row = (y / self.cell_size).floor() as i64;
+        (col, row)
+    }
+
+    fn insert(&mut self, pt: Point, idx: usize) {
+        let cell = self.cell(pt);
+        self.cells.insert(cell, idx);
+    }
+
+    fn neighbors(&self, pt: Point) -> impl Iterator<Item = usize> + '_ {
+        let (col, row) = self.cell(pt);
+        (row - 2..=row + 2)
+            .flat_map(move |r| (col - 2..=col + 2).map(move |c| (c, r)))
+            .filter_map(|cell| self.cells.get(&cell).copied())
+    }
+}
+
+fn annulus_point(center: Point, radius: f64) -> Point {
+    let (x, y) = center;
+    let dist = random_in_range((radius, 2. * radius));
+    let angle = random_in_range((0., 2. * std::f64::consts::PI));
+    (x + dist * angle.cos(), y + dist * angle.sin())
+}
+
+fn in_range(v: f64, range: (f64, f64)) -> bool {
+    let (min, max) = range;
+    v >= min && v <= max
+}
+
+fn squared_distance(p1: Point, p2: Point) -> f64 {
+    let dx = p1.0 - p2.0;
+    let dy = p1.1 - p2.1;
+    (dx * dx) + (dy * dy)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_minimum_separation() {
+        let radius = 1.5;
+        let samples = poisson_disk_samples((-10., 10.), (-5., 5.), radius, 100000);
+        assert!(samples.points.len() > 10);
+        for (i, p1) in samples.points.iter().enumerate() {
+            assert!(in_range(p1.0, (-10., 10.)) && in_range(p1.1, (-5., 5.)));
+            for p2 in &samples.points[i + 1..] {
+                assert!(squared_distance(*p1, *p2) >= radius * radius);
+            }
+        }
+    }
+
+    #[test]
+    fn test_bounded_attempts() {
+        let samples = poisson_disk_samples((-100., 100.), (-100., 100.), 0.5, 50);
+        assert!(samples.points.len() <= 50);
+    }
+}
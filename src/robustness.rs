@@ -0,0 +1,141 @@
+use crate::build_graph::{self, NetGraph, Network};
+use crate::trial_statistics::TrialFailure;
+use serde::Deserialize;
+use simplegraph::{Graph, GraphVisitor};
+
+/// The terminal sections of an open line are always bridges: the
+/// requirements apply to the core of the network, what is left
+/// after repeatedly removing the ordinary stations (served by a
+/// single line, without transfers) with a single neighbour.
+#[derive(Clone, Copy, Deserialize)]
+pub enum Robustness {
+    /// Only list bridges and articulation stations.
+    #[serde(rename = "report")]
+    Report,
+    /// Reject networks with a bridge in the core.
+    #[serde(rename = "two_edge_connected")]
+    TwoEdgeConnected,
+    /// Reject networks with an articulation station in the core.
+    #[serde(rename = "biconnected")]
+    Biconnected,
+}
+
+/// List all the bridges and articulation stations of
+/// *net* and check its core against the requirement.
+pub fn apply_robustness(mut net: Network, req: Robustness) -> Result<Network, TrialFailure> {
+    let fails = match req {
+        Robustness::Report => false,
+        Robustness::TwoEdgeConnected | Robustness::Biconnected => {
+            let core = core_graph(&net.graph, &interchanges(&net));
+            let cuts = build_graph::find_cuts(&core);
+            let empty = (&core).node_count() < 3;
+            match req {
+                Robustness::TwoEdgeConnected => empty || !cuts.bridges.is_empty(),
+                _ => empty || !cuts.articulation_points.is_empty(),
+            }
+        }
+    };
+    if fails {
+        return Err(TrialFailure::NotRobust);
+    }
+    let cuts = build_graph::find_cuts(&net.graph);
+    net.bridges = cuts.bridges;
+    net.articulation_stations = cuts.articulation_points;
+    Ok(net)
+}
+
+fn interchanges(net: &Network) -> Vec<bool> {
+    let mut output: Vec<bool> = net.station_lines().iter().map(|l| l.len() > 1).collect();
+    for transfer in &net.transfers {
+        let (i, j) = transfer.stations;
+        output[i] = true;
+        output[j] = true;
+    }
+    output
+}
+
+/// Subgraph left after pruning the nodes with a
+/// single neighbour, except for the *kept* ones.
+fn core_graph(g: &NetGraph, kept: &[bool]) -> NetGraph {
+    let nodes = g.node_count();
+    let neighbours: Vec<Vec<usize>> = (0..nodes)
+        .map(|i| {
+            let mut n: Vec<usize> = g
+                .successor_iterator(i)
+                .map(|(_, j, _)| j)
+                .filter(|j| *j != i)
+                .collect();
+            n.sort_unstable();
+            n
+        })
+        .collect();
+    let mut degree: Vec<usize> = neighbours
+        .iter()
+        .map(|n| {
+            let mut n = n.clone();
+            n.dedup();
+            n.len()
+        })
+        .collect();
+    let mut removed = vec![false; nodes];
+    let prunable = |i: &usize| !kept[*i] && degree[*i] <= 1;
+    let mut queue: Vec<usize> = (0..nodes).filter(prunable).collect();
+    while let Some(i) = queue.pop() {
+        if removed[i] {
+            continue;
+        }
+        removed[i] = true;
+        let mut n = neighbours[i].clone();
+        n.dedup();
+        for j in n.into_iter().filter(|j| !removed[*j]) {
+            degree[j] -= 1;
+            if !kept[j] && degree[j] <= 1 {
+                queue.push(j);
+            }
+        }
+    }
+
+    let mut local = vec![None; nodes];
+    let core: Vec<usize> = (0..nodes).filter(|i| !removed[*i]).collect();
+    for (k, i) in core.iter().enumerate() {
+        local[*i] = Some(k);
+    }
+    let mut output = NetGraph::new_undirect(core.len());
+    for i in &core {
+        for j in neighbours[*i].iter().filter(|j| **j > *i) {
+            if let (Some(a), Some(b)) = (local[*i], local[*j]) {
+                output.add_new_default_arc(a, b);
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_core_graph() {
+        // a ring 0-1-2-3 with a tail 3-4-5 and a pendant 1-6
+        let mut graph = NetGraph::new_undirect(7);
+        for (i, j) in [(0, 1), (1, 2), (2, 3), (3, 0), (3, 4), (4, 5), (1, 6)] {
+            graph.add_new_default_arc(i, j);
+        }
+        let mut kept = vec![false; 7];
+        let core = core_graph(&graph, &kept);
+        assert_eq!((&core).node_count(), 4);
+        let cuts = build_graph::find_cuts(&core);
+        assert!(cuts.bridges.is_empty() && cuts.articulation_points.is_empty());
+
+        kept[4] = true;
+        let core = core_graph(&graph, &kept);
+        assert_eq!((&core).node_count(), 5);
+        assert_eq!(build_graph::find_cuts(&core).bridges, vec![(3, 4)]);
+
+        let cuts = build_graph::find_cuts(&graph);
+        assert_eq!(cuts.bridges, vec![(1, 6), (3, 4), (4, 5)]);
+        assert_eq!(cuts.articulation_points, vec![1, 3, 4]);
+    }
+}
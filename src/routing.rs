@@ -0,0 +1,416 @@
+use crate::build_graph::{Network, TransferKind};
+use crate::shortest_path;
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::fmt;
+use std::str::FromStr;
+
+/// How to rank the journeys between two stations.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Criteria {
+    /// Fastest journey, then fewest transfers.
+    Time,
+    /// Fewest transfers, then fastest journey.
+    Transfers,
+    /// All the journeys not beaten on both time and transfers.
+    Pareto,
+}
+
+impl FromStr for Criteria {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "time" => Ok(Self::Time),
+            "transfers" => Ok(Self::Transfers),
+            "pareto" => Ok(Self::Pareto),
+            _ => Err(format!(
+                "unknown criteria `{s}`: use time, transfers or pareto"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Journey {
+    pub time: f64,
+    pub transfers: usize,
+    pub legs: Vec<Leg>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Leg {
+    /// Ride on the given service pattern, through *stations*.
+    Ride {
+        line: usize,
+        pattern: usize,
+        stations: Vec<usize>,
+    },
+    Walk {
+        stations: (usize, usize),
+    },
+}
+
+#[derive(Debug)]
+pub struct UnknownStation(pub usize);
+
+impl fmt::Display for UnknownStation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "station {} does not exist", self.0)
+    }
+}
+
+impl std::error::Error for UnknownStation {}
+
+/// Find the journeys from *origin* to *destination*. Travel times
+/// are the arc weights plus, when line frequencies are known, the
+/// expected wait at each boarding; changing service pattern is a transfer.
+/// The result is empty when *destination* cannot be reached,
+/// Pareto journeys are sorted by time.
+pub fn plan(
+    net: &Network,
+    origin: usize,
+    destination: usize,
+    criteria: Criteria,
+) -> Result<Vec<Journey>, UnknownStation> {
+    for s in [origin, destination] {
+        if s >= net.points.len() {
+            return Err(UnknownStation(s));
+        }
+    }
+    let mut journeys = Planner::new(net).pareto_journeys(origin, destination);
+    let journeys = match criteria {
+        Criteria::Pareto => journeys,
+        Criteria::Time => journeys.into_iter().take(1).collect(),
+        Criteria::Transfers => journeys.pop().into_iter().collect(),
+    };
+    Ok(journeys)
+}
+
+/// Search state: waiting at a station or
+/// riding a pattern in the given direction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum State {
+    Station(usize),
+    Ride {
+        pattern: usize,
+        position: usize,
+        forward: bool,
+    },
+}
+
+struct Label {
+    time: f64,
+    boardings: usize,
+    state: State,
+    pred: Option<usize>,
+}
+
+/// Queue entry: lower time, then fewer boardings, is greater.
+struct LabelEntry {
+    time: f64,
+    boardings: usize,
+    label: usize,
+}
+
+impl PartialEq for LabelEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for LabelEntry {}
+
+impl Ord for LabelEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .time
+            .total_cmp(&self.time)
+            .then_with(|| other.boardings.cmp(&self.boardings))
+            .then_with(|| other.label.cmp(&self.label))
+    }
+}
+
+impl PartialOrd for LabelEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+struct Planner<'a> {
+    net: &'a Network,
+    /// Patterns and positions serving each station.
+    stops: Vec<Vec<(usize, usize)>>,
+    /// Number of stops in the patterns before each pattern.
+    offsets: Vec<usize>,
+    walks: Vec<Vec<(usize, f64)>>,
+    /// Wait time of each station.
+    waits: Vec<f64>,
+    labels: Vec<Label>,
+    queue: BinaryHeap<LabelEntry>,
+}
+
+impl<'a> Planner<'a> {
+    fn new(net: &'a Network) -> Self {
+        let mut stops = vec![vec![]; net.points.len()];
+        let mut offsets = vec![0];
+        for (p, pattern) in net.lines.iter().enumerate() {
+            for (k, s) in pattern.iter().enumerate() {
+                stops[*s].push((p, k));
+            }
+            offsets.push(offsets[p] + pattern.len());
+        }
+        let mut walks = vec![vec![]; net.points.len()];
+        for t in net
+            .transfers
+            .iter()
+            .filter(|t| t.kind == TransferKind::Walking)
+        {
+            let (i, j) = t.stations;
+            walks[i].push((j, t.distance));
+            walks[j].push((i, t.distance));
+        }
+        Self {
+            net,
+            stops,
+            offsets,
+            walks,
+            waits: net.graph.node_iterator().map(|(_, w)| w).collect(),
+            labels: vec![],
+            queue: BinaryHeap::new(),
+        }
+    }
+
+    /// Multi-criteria label setting search: labels leave the queue
+    /// by increasing time, so a label is kept only when it needs
+    /// fewer boardings than all those already kept for its state.
+    fn pareto_journeys(&mut self, origin: usize, destination: usize) -> Vec<Journey> {
+        let mut best_boardings: Vec<Option<usize>> = vec![None; self.state_count()];
+        let mut found = vec![];
+        self.push(0., 0, State::Station(origin), None);
+        while let Some(entry) = self.queue.pop() {
+            let Label {
+                time,
+                boardings,
+                state,
+                ..
+            } = self.labels[entry.label];
+            let best = &mut best_boardings[self.state_index(state)];
+            if best.is_some_and(|b| b <= boardings) {
+                continue;
+            }
+            *best = Some(boardings);
+            if state == State::Station(destination) {
+                found.push(entry.label);
+            }
+            self.expand(entry.label, time, boardings, state);
+        }
+        found.into_iter().map(|l| self.journey(l)).collect()
+    }
+
+    fn expand(&mut self, label: usize, time: f64, boardings: usize, state: State) {
+        match state {
+            State::Station(s) => {
+                for k in 0..self.walks[s].len() {
+                    let (next, dist) = self.walks[s][k];
+                    self.push(time + dist, boardings, State::Station(next), Some(label));
+                }
+                for k in 0..self.stops[s].len() {
+                    let (pattern, position) = self.stops[s][k];
+                    for forward in [true, false] {
+                        let ride = State::Ride {
+                            pattern,
+                            position,
+                            forward,
+                        };
+                        if self.next_stop(ride).is_some() {
+                            let wait = self.net.boarding_wait(pattern, self.waits[s]);
+                            self.push(time + wait, boardings + 1, ride, Some(label));
+                        }
+                    }
+                }
+            }
+            State::Ride {
+                pattern, position, ..
+            } => {
+                let station = self.net.lines[pattern][position];
+                self.push(time, boardings, State::Station(station), Some(label));
+                if let Some((next, cost)) = self.next_stop(state) {
+                    self.push(time + cost, boardings, next, Some(label));
+                }
+            }
+        }
+    }
+
+    /// The following stop of a ride with the travel time to it.
+    fn next_stop(&self, state: State) -> Option<(State, f64)> {
+        let State::Ride {
+            pattern,
+            position,
+            forward,
+        } = state
+        else {
+            return None;
+        };
+        let line = &self.net.lines[pattern];
+        let next = if forward {
+            Some(position + 1).filter(|n| *n < line.len())
+        } else {
+            position.checked_sub(1)
+        }?;
+        let cost = shortest_path::arc_weight(&self.net.graph, line[position], line[next])?;
+        let state = State::Ride {
+            pattern,
+            position: next,
+            forward,
+        };
+        Some((state, cost))
+    }
+
+    fn push(&mut self, time: f64, boardings: usize, state: State, pred: Option<usize>) {
+        let label = self.labels.len();
+        self.labels.push(Label {
+            time,
+            boardings,
+            state,
+            pred,
+        });
+        self.queue.push(LabelEntry {
+            time,
+            boardings,
+            label,
+        });
+    }
+
+    fn state_count(&self) -> usize {
+        self.net.points.len() + 2 * self.offsets[self.net.lines.len()]
+    }
+
+    fn state_index(&self, state: State) -> usize {
+        match state {
+            State::Station(s) => s,
+            State::Ride {
+                pattern,
+                position,
+                forward,
+            } => {
+                let k = 2 * (self.offsets[pattern] + position) + forward as usize;
+                self.net.points.len() + k
+            }
+        }
+    }
+
+    fn journey(&self, label: usize) -> Journey {
+        let mut states = vec![];
+        let mut curr = Some(label);
+        while let Some(l) = curr {
+            states.push(self.labels[l].state);
+            curr = self.labels[l].pred;
+        }
+        states.reverse();
+
+        let mut legs = vec![];
+        let mut prev_station = None;
+        for state in states {
+            match state {
+                State::Station(s) => {
+                    if let Some(p) = prev_station.filter(|p| *p != s) {
+                        legs.push(Leg::Walk { stations: (p, s) });
+                    }
+                    prev_station = Some(s);
+                }
+                State::Ride {
+                    pattern, position, ..
+                } => {
+                    let station = self.net.lines[pattern][position];
+                    match legs.last_mut() {
+                        Some(Leg::Ride {
+                            pattern: p,
+                            stations,
+                            ..
+                        }) if *p == pattern && prev_station.is_none() => stations.push(station),
+                        _ => legs.push(Leg::Ride {
+                            line: self.net.line_ids[pattern],
+                            pattern,
+                            stations: vec![station],
+                        }),
+                    }
+                    prev_station = None;
+                }
+            }
+        }
+        let Label {
+            time, boardings, ..
+        } = self.labels[label];
+        Journey {
+            time,
+            transfers: boardings.saturating_sub(1),
+            legs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::build_graph::{test_network, LineFrequency};
+
+    #[test]
+    fn test_pareto_journeys() {
+        // line 0: 0 - 1 - 2 - 3, slow on 1 - 2
+        // line 1: 1 - 4 - 2, faster but needs two transfers
+        let lines = vec![vec![0, 1, 2, 3], vec![1, 4, 2]];
+        let net = test_network(
+            5,
+            lines,
+            &[(0, 1, 1.), (1, 2, 10.), (2, 3, 1.), (1, 4, 2.), (4, 2, 2.)],
+        );
+        let journeys = plan(&net, 0, 3, Criteria::Pareto).unwrap();
+        let summary: Vec<(f64, usize)> = journeys.iter().map(|j| (j.time, j.transfers)).collect();
+        assert_eq!(summary, vec![(6., 2), (12., 0)]);
+        assert_eq!(
+            journeys[1].legs,
+            vec![Leg::Ride {
+                line: 0,
+                pattern: 0,
+                stations: vec![0, 1, 2, 3]
+            }]
+        );
+        assert_eq!(journeys[0].legs.len(), 3);
+
+        let fastest = plan(&net, 0, 3, Criteria::Time).unwrap();
+        assert_eq!(fastest[0].time, 6.);
+        let fewest = plan(&net, 3, 0, Criteria::Transfers).unwrap();
+        assert_eq!((fewest[0].time, fewest[0].transfers), (12., 0));
+    }
+
+    #[test]
+    fn test_boarding_wait() {
+        let lines = vec![vec![0, 1, 2, 3], vec![1, 4, 2]];
+        let mut net = test_network(
+            5,
+            lines,
+            &[(0, 1, 1.), (1, 2, 10.), (2, 3, 1.), (1, 4, 2.), (4, 2, 2.)],
+        );
+        let wait = |boarding_wait| LineFrequency {
+            frequency: 0.5 / boarding_wait,
+            boarding_wait,
+        };
+        net.line_frequencies = vec![wait(1.), wait(4.)];
+        let journeys = plan(&net, 0, 3, Criteria::Pareto).unwrap();
+        let summary: Vec<(f64, usize)> = journeys.iter().map(|j| (j.time, j.transfers)).collect();
+        // the detour costs two more boardings of line 0 and one of line 1
+        assert_eq!(summary, vec![(12., 2), (13., 0)]);
+    }
+
+    #[test]
+    fn test_unreachable() {
+        let net = test_network(4, vec![vec![0, 1], vec![2, 3]], &[(0, 1, 1.), (2, 3, 1.)]);
+        assert!(plan(&net, 0, 3, Criteria::Pareto).unwrap().is_empty());
+        assert!(plan(&net, 0, 7, Criteria::Pareto).is_err());
+        let same = plan(&net, 1, 1, Criteria::Time).unwrap();
+        assert_eq!(same[0].legs, vec![]);
+    }
+}
@@ -0,0 +1,315 @@
+use crate::build_graph::{NetGraph, Network};
+use crate::shortest_path;
+use crate::MResult;
+use rand::prelude::*;
+use rand_distr::LogNormal;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Deserialize)]
+pub struct ScenarioConfig {
+    pub base_name: String,
+    /// Number of sampled scenarios, zero to save the distributions only.
+    #[serde(default)]
+    pub count: usize,
+    /// Coefficient of variation of the arc travel times.
+    pub arc_cv: f64,
+    /// Coefficient of variation of the station waits, `arc_cv` if missing.
+    pub wait_cv: Option<f64>,
+}
+
+/// A lognormal distribution with the given `mean`:
+/// `mu` and `sigma` are the parameters of the underlying normal.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct TimeDistribution {
+    pub mean: f64,
+    pub mu: f64,
+    pub sigma: f64,
+}
+
+impl TimeDistribution {
+    /// Distribution with the given mean and coefficient of variation,
+    /// `None` when the mean is not positive (e.g. a station without wait).
+    fn from_mean_cv(mean: f64, cv: f64) -> Option<Self> {
+        if mean <= 0. {
+            return None;
+        }
+        let variance = (1. + cv * cv).ln();
+        Some(Self {
+            mean,
+            mu: mean.ln() - variance / 2.,
+            sigma: variance.sqrt(),
+        })
+    }
+
+    fn lognormal(&self) -> MResult<LogNormal<f64>> {
+        Ok(LogNormal::new(self.mu, self.sigma)?)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ArcDistribution {
+    pub arc: (usize, usize),
+    pub distribution: Option<TimeDistribution>,
+}
+
+/// An arc of an express pattern, running on the track
+/// sections it skips: its weight is their sum times `factor`.
+#[derive(Debug, Serialize)]
+pub struct ExpressArc {
+    pub arc: (usize, usize),
+    /// Index, in `arcs`, of each section.
+    pub sections: Vec<usize>,
+    pub factor: f64,
+}
+
+/// A sample of every arc weight and station wait,
+/// in the order of the distributions.
+#[derive(Debug, Serialize)]
+pub struct Scenario {
+    pub arcs: Vec<f64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub express: Vec<f64>,
+    pub stations: Vec<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WeightScenarios {
+    pub arcs: Vec<ArcDistribution>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub express: Vec<ExpressArc>,
+    pub stations: Vec<Option<TimeDistribution>>,
+    pub scenarios: Vec<Scenario>,
+}
+
+/// Give each arc of *net* and each station wait a lognormal travel
+/// time around its deterministic weight, then sample `count`
+/// scenarios on the same topology. Weights that are zero stay zero.
+/// Express arcs are not sampled on their own: in each scenario
+/// they follow the sampled weights of the sections they skip.
+pub fn weight_scenarios(net: &Network, conf: &ScenarioConfig) -> MResult<WeightScenarios> {
+    let wait_cv = conf.wait_cv.unwrap_or(conf.arc_cv);
+    if conf.arc_cv < 0. || wait_cv < 0. {
+        return Err("negative coefficient of variation".into());
+    }
+    let skipped = express_sections(net);
+    let mut weights = arc_weights(&net.graph);
+    weights.retain(|arc, _| !skipped.contains_key(arc));
+    let index: BTreeMap<Section, usize> =
+        weights.keys().enumerate().map(|(k, a)| (*a, k)).collect();
+    let mut express = vec![];
+    for (arc, (w, path)) in skipped {
+        let mut sections = vec![];
+        let mut length = 0.;
+        for s in &path {
+            let (k, weight) = index
+                .get(s)
+                .zip(weights.get(s))
+                .ok_or_else(|| format!("express arc {arc:?} skips the missing section {s:?}"))?;
+            sections.push(*k);
+            length += weight;
+        }
+        express.push(ExpressArc {
+            arc,
+            sections,
+            factor: if length > 0. { w / length } else { 0. },
+        });
+    }
+    let arcs: Vec<ArcDistribution> = weights
+        .into_iter()
+        .map(|(arc, w)| ArcDistribution {
+            arc,
+            distribution: TimeDistribution::from_mean_cv(w, conf.arc_cv),
+        })
+        .collect();
+    let stations: Vec<Option<TimeDistribution>> = net
+        .graph
+        .node_iterator()
+        .map(|(_, w)| TimeDistribution::from_mean_cv(w, wait_cv))
+        .collect();
+
+    let arc_distr = samplers(arcs.iter().map(|a| a.distribution))?;
+    let station_distr = samplers(stations.iter().copied())?;
+    let mut rng = rand::thread_rng();
+    let scenarios = (0..conf.count)
+        .map(|_| {
+            let arcs = sample(&arc_distr, &mut rng);
+            let express = express
+                .iter()
+                .map(|e| e.factor * e.sections.iter().map(|s| arcs[*s]).sum::<f64>())
+                .collect();
+            Scenario {
+                arcs,
+                express,
+                stations: sample(&station_distr, &mut rng),
+            }
+        })
+        .collect();
+    Ok(WeightScenarios {
+        arcs,
+        express,
+        stations,
+        scenarios,
+    })
+}
+
+/// The distinct arcs between two stations, with their lowest weight.
+fn arc_weights(g: &NetGraph) -> BTreeMap<(usize, usize), f64> {
+    let mut weights = BTreeMap::new();
+    for (i, j, w) in g.arc_iterator().filter(|(i, j, _)| i < j) {
+        let weight = weights.entry((i, j)).or_insert(w);
+        *weight = f64::min(*weight, w);
+    }
+    weights
+}
+
+/// The stations at the ends of an arc, lowest first.
+type Section = (usize, usize);
+
+/// The arcs of the express patterns, with their weight and the
+/// sections they skip, taken from a pattern of the same line that
+/// stops at both ends. Arcs also ridden by an ordinary pattern are
+/// track sections; arcs without a pattern to follow are left out
+/// too, and sampled as any other arc.
+fn express_sections(net: &Network) -> BTreeMap<Section, (f64, Vec<Section>)> {
+    let ordinary = |q: &usize| !net.express_patterns.contains(q);
+    let tracks: BTreeSet<Section> = (0..net.lines.len())
+        .filter(ordinary)
+        .flat_map(|q| net.lines[q].windows(2))
+        .map(|s| (s[0].min(s[1]), s[0].max(s[1])))
+        .collect();
+    let mut output = BTreeMap::new();
+    for p in &net.express_patterns {
+        let line = net.line_ids[*p];
+        for pair in net.lines[*p].windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            if tracks.contains(&(a.min(b), a.max(b))) {
+                continue;
+            }
+            let path = net
+                .lines
+                .iter()
+                .enumerate()
+                .filter(|(q, _)| net.line_ids[*q] == line && ordinary(q))
+                .find_map(|(_, pattern)| {
+                    let x = pattern.iter().position(|s| *s == a)?;
+                    let y = pattern.iter().position(|s| *s == b)?;
+                    Some(&pattern[x.min(y)..=x.max(y)])
+                });
+            let weight = shortest_path::arc_weight(&net.graph, a, b);
+            if let (Some(path), Some(weight)) = (path, weight) {
+                let sections = path
+                    .windows(2)
+                    .map(|s| (s[0].min(s[1]), s[0].max(s[1])))
+                    .collect();
+                output.insert((a.min(b), a.max(b)), (weight, sections));
+            }
+        }
+    }
+    output
+}
+
+fn samplers<I>(distributions: I) -> MResult<Vec<Option<LogNormal<f64>>>>
+where
+    I: Iterator<Item = Option<TimeDistribution>>,
+{
+    distributions
+        .map(|d| d.as_ref().map(TimeDistribution::lognormal).transpose())
+        .collect()
+}
+
+fn sample<R: Rng>(distributions: &[Option<LogNormal<f64>>], rng: &mut R) -> Vec<f64> {
+    distributions
+        .iter()
+        .map(|d| d.as_ref().map_or(0., |d| d.sample(rng)))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::build_graph::test_network;
+    use simplegraph::Graph;
+
+    #[test]
+    fn test_distribution() {
+        let d = TimeDistribution::from_mean_cv(2., 0.5).unwrap();
+        // mean of the lognormal: exp(mu + sigma^2 / 2)
+        assert!(((d.mu + d.sigma * d.sigma / 2.).exp() - 2.).abs() < 1e-12);
+        let d = TimeDistribution::from_mean_cv(2., 0.).unwrap();
+        assert_eq!(d.sigma, 0.);
+        assert!(TimeDistribution::from_mean_cv(0., 0.5).is_none());
+    }
+
+    #[test]
+    fn test_weight_scenarios() {
+        let lines = vec![vec![0, 1, 2]];
+        let mut net = test_network(3, lines, &[(0, 1, 2.), (1, 2, 3.)]);
+        net.graph
+            .update_all_nodes_weight(|i, _| if i == 1 { 0.5 } else { 0. });
+        let conf = ScenarioConfig {
+            base_name: String::new(),
+            count: 2000,
+            arc_cv: 0.2,
+            wait_cv: Some(0.),
+        };
+        let output = weight_scenarios(&net, &conf).unwrap();
+        let arcs: Vec<(usize, usize)> = output.arcs.iter().map(|a| a.arc).collect();
+        assert_eq!(arcs, vec![(0, 1), (1, 2)]);
+        assert_eq!(output.stations[0], None);
+        assert_eq!(output.scenarios.len(), 2000);
+
+        let mean = output.scenarios.iter().map(|s| s.arcs[1]).sum::<f64>() / 2000.;
+        assert!((mean - 3.).abs() < 0.1, "{mean}");
+        for s in &output.scenarios {
+            assert!(s.arcs.iter().all(|w| *w > 0.));
+            assert_eq!(s.stations[0], 0.);
+            assert!((s.stations[1] - 0.5).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_express_follows_sections() {
+        // the express pattern 0 - 2 skips station 1 at half the time
+        let lines = vec![vec![0, 1, 2], vec![0, 2]];
+        let mut net = test_network(3, lines, &[(0, 1, 2.), (1, 2, 3.), (0, 2, 2.5)]);
+        net.line_ids = vec![0, 0];
+        net.express_patterns = vec![1];
+        let conf = ScenarioConfig {
+            base_name: String::new(),
+            count: 10,
+            arc_cv: 0.2,
+            wait_cv: None,
+        };
+        let output = weight_scenarios(&net, &conf).unwrap();
+        let arcs: Vec<(usize, usize)> = output.arcs.iter().map(|a| a.arc).collect();
+        assert_eq!(arcs, vec![(0, 1), (1, 2)]);
+        assert_eq!(output.express[0].arc, (0, 2));
+        assert_eq!(output.express[0].sections, vec![0, 1]);
+        assert_eq!(output.express[0].factor, 0.5);
+        for s in &output.scenarios {
+            assert!((s.express[0] - (s.arcs[0] + s.arcs[1]) / 2.).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_express_on_track_section() {
+        // line 1 runs on the arc 0 - 2 skipped by the express pattern
+        let lines = vec![vec![0, 1, 2], vec![0, 2], vec![0, 2]];
+        let mut net = test_network(3, lines, &[(0, 1, 2.), (1, 2, 3.), (0, 2, 2.5)]);
+        net.line_ids = vec![0, 1, 0];
+        net.express_patterns = vec![2];
+        let conf = ScenarioConfig {
+            base_name: String::new(),
+            count: 1,
+            arc_cv: 0.2,
+            wait_cv: None,
+        };
+        let output = weight_scenarios(&net, &conf).unwrap();
+        let arcs: Vec<(usize, usize)> = output.arcs.iter().map(|a| a.arc).collect();
+        assert_eq!(arcs, vec![(0, 1), (0, 2), (1, 2)]);
+        assert!(output.arcs.iter().all(|a| a.distribution.is_some()));
+        assert!(output.express.is_empty());
+    }
+}
@@ -0,0 +1,122 @@
+use crate::Curve;
+use flo_curves::{BezierCurve, Coordinate};
+use serde::Deserialize;
+
+const CUSP_TOLERANCE: f64 = 1e-6;
+
+#[derive(Clone, Copy, Deserialize, PartialEq)]
+pub enum SelfIntersectionPolicy {
+    #[serde(rename = "reject")]
+    Reject,
+    #[serde(rename = "resample")]
+    Resample,
+    #[serde(rename = "station")]
+    Station,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CurveDefect {
+    /// The curve crosses itself at the two given positions.
+    Loop(f64, f64),
+    /// The curve stops and turns back at the given position.
+    Cusp(f64),
+}
+
+/// Find the self intersection or the cusp of a cubic curve.
+/// Given the polynomial form B(t) = a t^3 + b t^2 + c t + d,
+/// B(s) = B(t) with s != t reduces to a(u^2 - v) + b u + c = 0
+/// where u = s + t and v = s t, which is linear once a is eliminated.
+pub fn find_defect(curve: &Curve) -> Option<CurveDefect> {
+    let (a, b, c) = polynomial_coefficients(curve);
+    let den = cross(a, b);
+    if den.abs() < f64::EPSILON {
+        return None;
+    }
+    let u = -cross(a, c) / den;
+    let v = if a.0.abs() > a.1.abs() {
+        u * u + (b.0 * u + c.0) / a.0
+    } else {
+        u * u + (b.1 * u + c.1) / a.1
+    };
+
+    let disc = u * u - 4. * v;
+    if disc < 0. {
+        return None;
+    }
+    let delta = disc.sqrt();
+    let (s, t) = ((u - delta) / 2., (u + delta) / 2.);
+    if s < 0. || t > 1. {
+        None
+    } else if delta < CUSP_TOLERANCE {
+        Some(CurveDefect::Cusp(u / 2.))
+    } else {
+        Some(CurveDefect::Loop(s, t))
+    }
+}
+
+type Vector = (f64, f64);
+
+fn polynomial_coefficients(curve: &Curve) -> (Vector, Vector, Vector) {
+    let (p1, p2) = curve.control_points();
+    let p0 = to_vector(curve.start_point());
+    let p1 = to_vector(p1);
+    let p2 = to_vector(p2);
+    let p3 = to_vector(curve.end_point());
+    let coeff =
+        |f: fn(f64, f64, f64, f64) -> f64| (f(p0.0, p1.0, p2.0, p3.0), f(p0.1, p1.1, p2.1, p3.1));
+    let a = coeff(|p0, p1, p2, p3| -p0 + 3. * p1 - 3. * p2 + p3);
+    let b = coeff(|p0, p1, p2, _| 3. * p0 - 6. * p1 + 3. * p2);
+    let c = coeff(|p0, p1, _, _| -3. * p0 + 3. * p1);
+    (a, b, c)
+}
+
+fn to_vector<C: Coordinate>(c: C) -> Vector {
+    (c.get(0), c.get(1))
+}
+
+fn cross(v1: Vector, v2: Vector) -> f64 {
+    v1.0 * v2.1 - v1.1 * v2.0
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use flo_curves::{bezier, BezierCurveFactory};
+
+    fn make_curve(pts: [(f64, f64); 4]) -> Curve {
+        bezier::Curve::from_points(pts[0].into(), (pts[1].into(), pts[2].into()), pts[3].into())
+    }
+
+    #[test]
+    fn test_loop() {
+        let curve = make_curve([(0., 0.), (3., 3.), (-2., 3.), (1., 0.)]);
+        let defect = find_defect(&curve);
+        if let Some(CurveDefect::Loop(s, t)) = defect {
+            let p1 = curve.point_at_pos(s);
+            let p2 = curve.point_at_pos(t);
+            assert!(s < t);
+            assert!((p1.get(0) - p2.get(0)).abs() < 1e-9);
+            assert!((p1.get(1) - p2.get(1)).abs() < 1e-9);
+        } else {
+            panic!("expected a loop, found {defect:?}");
+        }
+    }
+
+    #[test]
+    fn test_cusp() {
+        let curve = make_curve([(0., 0.), (1., 1.), (0., 1.), (1., 0.)]);
+        let defect = find_defect(&curve);
+        if let Some(CurveDefect::Cusp(t)) = defect {
+            assert!((t - 0.5).abs() < CUSP_TOLERANCE);
+        } else {
+            panic!("expected a cusp, found {defect:?}");
+        }
+    }
+
+    #[test]
+    fn test_arch() {
+        let curve = make_curve([(0., 0.), (0., 1.), (1., 1.), (1., 0.)]);
+        assert_eq!(find_defect(&curve), None);
+    }
+}
@@ -0,0 +1,124 @@
+use crate::build_graph::{NetGraph, Network, TransferKind};
+use crate::shortest_path;
+use simplegraph::Graph;
+use std::collections::HashMap;
+
+/// Directed graph of the trips on *net* with their line state: the
+/// first nodes are the stations, followed by a node for each stop of
+/// each pattern in each direction. Boarding a pattern goes from a
+/// station to one of its stops and costs the boarding wait (see
+/// `Network::boarding_wait`), riding follows the arcs of the pattern
+/// and alighting back to the station is free. Walking transfers link
+/// the stations directly. Sections of a pattern without an arc in
+/// the graph are left out.
+pub struct ServiceGraph {
+    pub graph: NetGraph,
+    /// The stations linked by each ride and walking arc of `graph`.
+    pub sections: HashMap<(usize, usize), (usize, usize)>,
+    stations: usize,
+    /// Index of the first stop of each pattern.
+    offsets: Vec<usize>,
+}
+
+impl ServiceGraph {
+    pub fn new(net: &Network) -> Self {
+        let stations = net.points.len();
+        let mut offsets = vec![];
+        let mut stops = 0;
+        for pattern in &net.lines {
+            offsets.push(stops);
+            stops += pattern.len();
+        }
+        let mut output = Self {
+            graph: NetGraph::new_direct(stations + 2 * stops),
+            sections: HashMap::new(),
+            stations,
+            offsets,
+        };
+        let waits: Vec<f64> = net.graph.node_iterator().map(|(_, w)| w).collect();
+        for (p, pattern) in net.lines.iter().enumerate() {
+            for forward in [true, false] {
+                for k in 0..pattern.len() {
+                    let stop = output.stop(p, k, forward);
+                    output.graph.add_new_arc(stop, pattern[k], 0.);
+                    let next = if forward {
+                        Some(k + 1).filter(|n| *n < pattern.len())
+                    } else {
+                        k.checked_sub(1)
+                    };
+                    let ride = next.and_then(|n| {
+                        shortest_path::arc_weight(&net.graph, pattern[k], pattern[n])
+                            .map(|w| (n, w))
+                    });
+                    if let Some((n, w)) = ride {
+                        let wait = net.boarding_wait(p, waits[pattern[k]]);
+                        output.graph.add_new_arc(pattern[k], stop, wait);
+                        let next_stop = output.stop(p, n, forward);
+                        output.graph.add_new_arc(stop, next_stop, w);
+                        output
+                            .sections
+                            .insert((stop, next_stop), (pattern[k], pattern[n]));
+                    }
+                }
+            }
+        }
+        for t in net
+            .transfers
+            .iter()
+            .filter(|t| t.kind == TransferKind::Walking)
+        {
+            let (i, j) = t.stations;
+            output.graph.add_new_arc(i, j, t.distance);
+            output.graph.add_new_arc(j, i, t.distance);
+            output.sections.insert((i, j), (i, j));
+            output.sections.insert((j, i), (j, i));
+        }
+        output
+    }
+
+    /// Node of the *k*-th stop of *pattern*, in the given direction.
+    pub fn stop(&self, pattern: usize, k: usize, forward: bool) -> usize {
+        self.stations + 2 * (self.offsets[pattern] + k) + forward as usize
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::build_graph::{test_network, LineFrequency};
+    use simplegraph::GraphVisitor;
+
+    #[test]
+    fn test_service_graph() {
+        let lines = vec![vec![0, 1, 2], vec![2, 3]];
+        let mut net = test_network(4, lines, &[(0, 1, 1.), (1, 2, 1.), (2, 3, 1.)]);
+        net.graph.update_all_nodes_weight(|i, _| i as f64);
+        let service = ServiceGraph::new(&net);
+        assert_eq!((&service.graph).node_count(), 4 + 2 * 5);
+        assert_eq!(service.stop(1, 0, false), 4 + 2 * 3);
+
+        let dist = shortest_path::dijkstra(&service.graph, 0).dist;
+        // the wait is paid when boarding only: 0 at station 0, 2 at station 2
+        assert_eq!(dist[..4], [0., 1., 2., 5.]);
+        assert_eq!(dist[service.stop(0, 2, true)], 2.);
+        assert_eq!(dist[service.stop(1, 0, true)], 4.);
+        let ride = (service.stop(0, 1, false), service.stop(0, 0, false));
+        assert_eq!(service.sections[&ride], (1, 0));
+    }
+
+    #[test]
+    fn test_line_boarding_wait() {
+        let lines = vec![vec![0, 1, 2], vec![2, 3]];
+        let mut net = test_network(4, lines, &[(0, 1, 1.), (1, 2, 1.), (2, 3, 1.)]);
+        net.graph.update_all_nodes_weight(|_, _| 10.);
+        let wait = |boarding_wait| LineFrequency {
+            frequency: 0.5 / boarding_wait,
+            boarding_wait,
+        };
+        net.line_frequencies = vec![wait(1.), wait(3.)];
+        let dist = shortest_path::dijkstra(&ServiceGraph::new(&net).graph, 0).dist;
+        // the station waits give way to the waits of the lines
+        assert_eq!(dist[..4], [0., 2., 3., 7.]);
+    }
+}
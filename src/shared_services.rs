@@ -0,0 +1,84 @@
+use crate::build_graph::{NetGraph, Network};
+use crate::shortest_path;
+
+/// Add *count* services running, along the shortest path on the
+/// tracks, between terminals of two different lines. These services
+/// share their sections with the lines they cross; they never run
+/// on walking transfers.
+pub fn add_shared_services(mut net: Network, count: usize) -> Network {
+    let terminals = terminals(&net);
+    let tracks = net.track_graph();
+    let mut next_line = net.line_ids.iter().max().map_or(0, |l| l + 1);
+    for _ in 0..count {
+        if let Some(service) = make_service(&tracks, &terminals) {
+            net.lines.push(service);
+            net.line_ids.push(next_line);
+            next_line += 1;
+        }
+    }
+    net
+}
+
+fn make_service(tracks: &NetGraph, terminals: &[(usize, usize)]) -> Option<Vec<usize>> {
+    if terminals.is_empty() {
+        return None;
+    }
+    let (src, src_line) = terminals[fastrand::usize(..terminals.len())];
+    let candidates: Vec<usize> = terminals
+        .iter()
+        .filter(|(_, line)| *line != src_line)
+        .map(|(s, _)| *s)
+        .collect();
+    if candidates.is_empty() {
+        return None;
+    }
+    let dst = candidates[fastrand::usize(..candidates.len())];
+    shortest_path::dijkstra(tracks, src).path_to(dst)
+}
+
+/// First and last station of each service pattern, with its line.
+fn terminals(net: &Network) -> Vec<(usize, usize)> {
+    let mut output = vec![];
+    for (pattern, line) in net.lines.iter().zip(&net.line_ids) {
+        for s in [pattern.first(), pattern.last()].into_iter().flatten() {
+            if !output.contains(&(*s, *line)) {
+                output.push((*s, *line));
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::build_graph::{line_arcs, test_network, Transfer, TransferKind};
+    use simplegraph::Graph;
+
+    #[test]
+    fn test_services_on_tracks() {
+        // line 0: 0 - 1 - 2, line 1: 2 - 3 - 4, a short walk from 0 to 4
+        let lines = vec![vec![0, 1, 2], vec![2, 3, 4]];
+        let mut net = test_network(5, lines.clone(), &line_arcs(&lines, 1.));
+        net.graph.add_new_arc(0, 4, 0.5);
+        net.transfers.push(Transfer {
+            stations: (0, 4),
+            distance: 0.5,
+            kind: TransferKind::Walking,
+        });
+        let net = add_shared_services(net, 20);
+        assert_eq!(net.lines.len(), 22);
+        for service in &net.lines[2..] {
+            for p in service.windows(2) {
+                assert_eq!((p[0] as isize - p[1] as isize).abs(), 1, "{service:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_no_terminals() {
+        let net = test_network(0, vec![], &[]);
+        assert!(add_shared_services(net, 3).lines.is_empty());
+    }
+}
@@ -0,0 +1,202 @@
+use crate::build_graph::NetGraph;
+use simplegraph::GraphVisitor;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashSet};
+
+pub struct ShortestPaths {
+    pub dist: Vec<f64>,
+    pub pred: Vec<Option<usize>>,
+}
+
+impl ShortestPaths {
+    /// Return the nodes from the source to *dst*, both included.
+    pub fn path_to(&self, dst: usize) -> Option<Vec<usize>> {
+        if self.dist[dst].is_infinite() {
+            return None;
+        }
+        let mut path = vec![dst];
+        let mut curr = dst;
+        while let Some(prev) = self.pred[curr] {
+            path.push(prev);
+            curr = prev;
+        }
+        path.reverse();
+        Some(path)
+    }
+}
+
+pub fn dijkstra(g: &NetGraph, src: usize) -> ShortestPaths {
+    dijkstra_by(g, src, |_, _, w| w)
+}
+
+/// Dijkstra search where the cost of arc (i, j) with
+/// weight w is given by *arc_cost*(i, j, w).
+pub fn dijkstra_by<F>(g: &NetGraph, src: usize, arc_cost: F) -> ShortestPaths
+where
+    F: Fn(usize, usize, f64) -> f64,
+{
+    let nodes = g.node_count();
+    let mut dist = vec![f64::INFINITY; nodes];
+    let mut pred = vec![None; nodes];
+    let mut queue = BinaryHeap::new();
+    dist[src] = 0.;
+    queue.push(QueueEntry::new(src, 0.));
+
+    while let Some(QueueEntry { node, cost }) = queue.pop() {
+        if cost > dist[node] {
+            continue;
+        }
+        for (_, next, w) in g.successor_iterator(node) {
+            let next_cost = cost + arc_cost(node, next, w);
+            if next_cost < dist[next] {
+                dist[next] = next_cost;
+                pred[next] = Some(node);
+                queue.push(QueueEntry::new(next, next_cost));
+            }
+        }
+    }
+
+    ShortestPaths { dist, pred }
+}
+
+/// Yen's algorithm: up to *k* loopless paths from *src* to *dst*,
+/// by non decreasing cost, each one with its cost.
+pub fn k_shortest_paths(g: &NetGraph, src: usize, dst: usize, k: usize) -> Vec<(Vec<usize>, f64)> {
+    let mut found: Vec<(Vec<usize>, f64)> = vec![];
+    if k == 0 {
+        return found;
+    }
+    let first = match dijkstra(g, src).path_to(dst) {
+        Some(path) => path,
+        None => return found,
+    };
+    found.push((first.clone(), path_cost(g, &first)));
+    let mut candidates: Vec<(Vec<usize>, f64)> = vec![];
+    while found.len() < k {
+        let last = found.last().unwrap().0.clone();
+        for i in 0..last.len() - 1 {
+            let root = &last[..=i];
+            let removed_arcs: HashSet<(usize, usize)> = found
+                .iter()
+                .filter(|(p, _)| p.len() > i + 1 && p[..=i] == *root)
+                .map(|(p, _)| (p[i], p[i + 1]))
+                .collect();
+            let removed_nodes: HashSet<usize> = root[..i].iter().copied().collect();
+            let spur = dijkstra_by(g, last[i], |a, b, w| {
+                if removed_nodes.contains(&b) || removed_arcs.contains(&(a, b)) {
+                    f64::INFINITY
+                } else {
+                    w
+                }
+            });
+            if let Some(spur_path) = spur.path_to(dst) {
+                let mut path = root.to_vec();
+                path.extend_from_slice(&spur_path[1..]);
+                let known = |(p, _): &(Vec<usize>, f64)| *p == path;
+                if !found.iter().any(known) && !candidates.iter().any(known) {
+                    let cost = path_cost(g, &path);
+                    candidates.push((path, cost));
+                }
+            }
+        }
+        let best = candidates
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.1.total_cmp(&b.1))
+            .map(|(k, _)| k);
+        match best {
+            Some(best) => found.push(candidates.swap_remove(best)),
+            None => break,
+        }
+    }
+    found
+}
+
+/// Cheapest arc from *i* to *j*, if any.
+pub fn arc_weight(g: &NetGraph, i: usize, j: usize) -> Option<f64> {
+    g.successor_iterator(i)
+        .filter(|(_, next, _)| *next == j)
+        .map(|(_, _, w)| w)
+        .min_by(f64::total_cmp)
+}
+
+/// Sum of the cheapest arcs along *path*, infinite when an arc is missing.
+pub fn path_cost(g: &NetGraph, path: &[usize]) -> f64 {
+    path.windows(2)
+        .map(|p| arc_weight(g, p[0], p[1]).unwrap_or(f64::INFINITY))
+        .sum()
+}
+
+/// Priority queue entry: the node with the lowest cost is the greatest.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QueueEntry {
+    pub node: usize,
+    pub cost: f64,
+}
+
+impl QueueEntry {
+    pub fn new(node: usize, cost: f64) -> Self {
+        Self { node, cost }
+    }
+}
+
+impl Eq for QueueEntry {}
+
+impl Ord for QueueEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .cost
+            .total_cmp(&self.cost)
+            .then_with(|| other.node.cmp(&self.node))
+    }
+}
+
+impl PartialOrd for QueueEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use simplegraph::Graph;
+
+    #[test]
+    fn test_dijkstra() {
+        let mut graph = NetGraph::new_undirect(5);
+        graph.add_new_arc(0, 1, 1.);
+        graph.add_new_arc(1, 2, 1.);
+        graph.add_new_arc(0, 2, 3.);
+        graph.add_new_arc(2, 3, 2.);
+
+        let paths = dijkstra(&graph, 0);
+        assert_eq!(paths.dist[..4], [0., 1., 2., 4.]);
+        assert!(paths.dist[4].is_infinite());
+        assert_eq!(paths.path_to(3), Some(vec![0, 1, 2, 3]));
+        assert_eq!(paths.path_to(0), Some(vec![0]));
+        assert_eq!(paths.path_to(4), None);
+    }
+
+    #[test]
+    fn test_k_shortest_paths() {
+        // 0 - 1 - 3 costs 2, 0 - 2 - 3 costs 3
+        let mut graph = NetGraph::new_undirect(5);
+        graph.add_new_arc(0, 1, 1.);
+        graph.add_new_arc(1, 3, 1.);
+        graph.add_new_arc(0, 2, 2.);
+        graph.add_new_arc(2, 3, 1.);
+        graph.add_new_arc(1, 2, 2.);
+
+        let paths = k_shortest_paths(&graph, 0, 3, 10);
+        let costs: Vec<f64> = paths.iter().map(|(_, c)| *c).collect();
+        assert_eq!(paths[0].0, vec![0, 1, 3]);
+        assert_eq!(paths[1].0, vec![0, 2, 3]);
+        // then 0 - 1 - 2 - 3 and 0 - 2 - 1 - 3
+        assert_eq!(costs, vec![2., 3., 4., 5.]);
+        assert_eq!(k_shortest_paths(&graph, 0, 3, 1).len(), 1);
+        assert!(k_shortest_paths(&graph, 0, 3, 0).is_empty());
+        assert!(k_shortest_paths(&graph, 0, 4, 3).is_empty());
+    }
+}
@@ -0,0 +1,247 @@
+use crate::build_graph::{Network, TransferKind};
+use crate::service_graph::ServiceGraph;
+use crate::shortest_path;
+use serde::Deserialize;
+use std::io::{self, Write};
+
+/// Output of the origin-destination matrices: the base
+/// name of the files, as text or as compact binary data.
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SkimFormat {
+    Csv(String),
+    Binary(String),
+}
+
+impl SkimFormat {
+    pub fn base_name(&self) -> &str {
+        match self {
+            Self::Csv(name) | Self::Binary(name) => name,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv(_) => "csv",
+            Self::Binary(_) => "bin",
+        }
+    }
+}
+
+/// Compute, one origin at a time, the travel time and
+/// the minimum number of transfers to every station.
+pub struct Skim<'a> {
+    net: &'a Network,
+    service: ServiceGraph,
+    /// Patterns serving each station.
+    stops: Vec<Vec<usize>>,
+    walks: Vec<Vec<usize>>,
+}
+
+impl<'a> Skim<'a> {
+    pub fn new(net: &'a Network) -> Self {
+        let mut stops = vec![vec![]; net.points.len()];
+        for (p, pattern) in net.lines.iter().enumerate() {
+            for s in pattern {
+                stops[*s].push(p);
+            }
+        }
+        let mut walks = vec![vec![]; net.points.len()];
+        for t in &net.transfers {
+            if t.kind == TransferKind::Walking {
+                let (i, j) = t.stations;
+                walks[i].push(j);
+                walks[j].push(i);
+            }
+        }
+        Self {
+            net,
+            service: ServiceGraph::new(net),
+            stops,
+            walks,
+        }
+    }
+
+    pub fn station_count(&self) -> usize {
+        self.net.points.len()
+    }
+
+    /// Shortest travel time from *origin*: the arc weights plus the
+    /// wait time of each station where a train is taken, staying on
+    /// board is free. Unreachable stations are infinitely far.
+    pub fn times(&self, origin: usize) -> Vec<f64> {
+        let mut dist = shortest_path::dijkstra(&self.service.graph, origin).dist;
+        dist.truncate(self.station_count());
+        dist
+    }
+
+    /// Minimum number of changes of service pattern needed to
+    /// reach each station from *origin*, walking transfers
+    /// included. `None` for unreachable stations.
+    pub fn transfers(&self, origin: usize) -> Vec<Option<usize>> {
+        let mut boardings = vec![None; self.station_count()];
+        let mut boarded = vec![false; self.net.lines.len()];
+        boardings[origin] = Some(0);
+        let mut frontier = self.walk_closure(vec![origin], 0, &mut boardings);
+        let mut level = 0;
+        while !frontier.is_empty() {
+            level += 1;
+            let mut next = vec![];
+            for s in frontier {
+                for p in &self.stops[s] {
+                    if boarded[*p] {
+                        continue;
+                    }
+                    boarded[*p] = true;
+                    for t in &self.net.lines[*p] {
+                        if boardings[*t].is_none() {
+                            boardings[*t] = Some(level);
+                            next.push(*t);
+                        }
+                    }
+                }
+            }
+            frontier = self.walk_closure(next, level, &mut boardings);
+        }
+        boardings
+            .into_iter()
+            .map(|b| b.map(|b: usize| b.saturating_sub(1)))
+            .collect()
+    }
+
+    /// Add to *stations* those reachable on foot, with the same boardings.
+    fn walk_closure(
+        &self,
+        mut stations: Vec<usize>,
+        level: usize,
+        boardings: &mut [Option<usize>],
+    ) -> Vec<usize> {
+        let mut k = 0;
+        while k < stations.len() {
+            for t in &self.walks[stations[k]] {
+                if boardings[*t].is_none() {
+                    boardings[*t] = Some(level);
+                    stations.push(*t);
+                }
+            }
+            k += 1;
+        }
+        stations
+    }
+}
+
+/// Write the time and transfer matrices, one row per origin,
+/// in the given format. CSV leaves unreachable pairs empty.
+/// Binary files start with the station count as a little endian
+/// u64, followed by the rows as little endian f32 times (infinite
+/// when unreachable) or u16 transfers (u16::MAX when unreachable).
+pub fn write_skim<W: Write>(
+    net: &Network,
+    format: &SkimFormat,
+    mut times: W,
+    mut transfers: W,
+) -> io::Result<()> {
+    let skim = Skim::new(net);
+    let count = skim.station_count();
+    if let SkimFormat::Binary(_) = format {
+        times.write_all(&(count as u64).to_le_bytes())?;
+        transfers.write_all(&(count as u64).to_le_bytes())?;
+    }
+    for origin in 0..count {
+        let row_times = skim.times(origin);
+        let row_transfers = skim.transfers(origin);
+        match format {
+            SkimFormat::Csv(_) => {
+                write_csv_row(&mut times, row_times.iter().map(|t| finite(*t)))?;
+                write_csv_row(&mut transfers, row_transfers.into_iter())?;
+            }
+            SkimFormat::Binary(_) => {
+                for t in row_times {
+                    times.write_all(&(t as f32).to_le_bytes())?;
+                }
+                for t in row_transfers {
+                    let t = t.map_or(u16::MAX, |t| t.min(u16::MAX as usize - 1) as u16);
+                    transfers.write_all(&t.to_le_bytes())?;
+                }
+            }
+        }
+    }
+    times.flush()?;
+    transfers.flush()
+}
+
+fn finite(t: f64) -> Option<f64> {
+    Some(t).filter(|t| t.is_finite())
+}
+
+fn write_csv_row<W, T, I>(output: &mut W, row: I) -> io::Result<()>
+where
+    W: Write,
+    T: ToString,
+    I: Iterator<Item = Option<T>>,
+{
+    let cells: Vec<String> = row
+        .map(|c| c.map(|c| c.to_string()).unwrap_or_default())
+        .collect();
+    writeln!(output, "{}", cells.join(","))
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::build_graph::{test_network, Transfer};
+    use simplegraph::Graph;
+
+    #[test]
+    fn test_skim() {
+        // line 0: 0 - 1 - 2, line 1: 2 - 3, station 4 is a
+        // walk away from 3, station 5 is isolated
+        let lines = vec![vec![0, 1, 2], vec![2, 3]];
+        let arcs = [(0, 1, 1.), (1, 2, 1.), (2, 3, 1.), (3, 4, 0.5)];
+        let mut net = test_network(6, lines, &arcs);
+        net.graph.update_all_nodes_weight(|_, _| 0.25);
+        net.transfers = vec![Transfer {
+            stations: (3, 4),
+            distance: 0.5,
+            kind: TransferKind::Walking,
+        }];
+
+        let skim = Skim::new(&net);
+        let times = skim.times(0);
+        // the wait is paid at stations 0 and 2, where a train is taken
+        assert_eq!(times[..5], [0., 1.25, 2.25, 3.5, 4.]);
+        assert!(times[5].is_infinite());
+        let transfers = skim.transfers(0);
+        assert_eq!(
+            transfers,
+            vec![Some(0), Some(0), Some(0), Some(1), Some(1), None]
+        );
+        assert_eq!(skim.transfers(4)[..4], [Some(1), Some(1), Some(0), Some(0)]);
+
+        let mut times = vec![];
+        let mut transfers = vec![];
+        write_skim(
+            &net,
+            &SkimFormat::Csv("".into()),
+            &mut times,
+            &mut transfers,
+        )
+        .unwrap();
+        let transfers = String::from_utf8(transfers).unwrap();
+        assert_eq!(transfers.lines().next(), Some("0,0,0,1,1,"));
+        assert_eq!(transfers.lines().count(), 6);
+
+        let mut times = vec![];
+        let mut transfers = vec![];
+        write_skim(
+            &net,
+            &SkimFormat::Binary("".into()),
+            &mut times,
+            &mut transfers,
+        )
+        .unwrap();
+        assert_eq!(times.len(), 8 + 36 * 4);
+        assert_eq!(transfers.len(), 8 + 36 * 2);
+    }
+}
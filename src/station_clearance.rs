@@ -0,0 +1,90 @@
+use crate::build_graph::{Network, Pt, Transfer, TransferKind};
+use crate::trial_statistics::TrialFailure;
+use serde::Deserialize;
+use simplegraph::Graph;
+
+#[derive(Deserialize)]
+pub struct StationClearanceConfig {
+    pub distance: f64,
+    pub policy: ClearancePolicy,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+pub enum ClearancePolicy {
+    #[serde(rename = "reject")]
+    Reject,
+    #[serde(rename = "walking_transfer")]
+    WalkingTransfer,
+    #[serde(rename = "out_of_station")]
+    OutOfStation,
+}
+
+pub fn apply_clearance(
+    mut net: Network,
+    conf: &StationClearanceConfig,
+) -> Result<Network, TrialFailure> {
+    let close = close_stations(&net.points, &net.station_lines(), conf.distance);
+    let kind = match conf.policy {
+        ClearancePolicy::Reject if close.is_empty() => return Ok(net),
+        ClearancePolicy::Reject => return Err(TrialFailure::StationClearance),
+        ClearancePolicy::WalkingTransfer => TransferKind::Walking,
+        ClearancePolicy::OutOfStation => TransferKind::OutOfStation,
+    };
+
+    for (stations, distance) in close {
+        if kind == TransferKind::Walking {
+            let (i, j) = stations;
+            net.graph.add_new_arc(i, j, distance);
+        }
+        net.transfers.push(Transfer {
+            stations,
+            distance,
+            kind,
+        });
+    }
+    Ok(net)
+}
+
+/// Find all the pairs of stations closer than *distance*
+/// that do not share any line.
+fn close_stations(
+    pts: &[Pt],
+    station_lines: &[Vec<usize>],
+    distance: f64,
+) -> Vec<((usize, usize), f64)> {
+    let mut output = vec![];
+    for (i, p1) in pts.iter().enumerate() {
+        for (j, p2) in pts.iter().enumerate().skip(i + 1) {
+            let dist = euclid_distance(*p1, *p2);
+            if dist < distance && !share_line(&station_lines[i], &station_lines[j]) {
+                output.push(((i, j), dist));
+            }
+        }
+    }
+    output
+}
+
+fn share_line(a: &[usize], b: &[usize]) -> bool {
+    a.iter().any(|l| b.contains(l))
+}
+
+fn euclid_distance(p1: Pt, p2: Pt) -> f64 {
+    let (x1, y1) = p1;
+    let (x2, y2) = p2;
+    (x1 - x2).hypot(y1 - y2)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_close_stations() {
+        let pts = vec![(0., 0.), (0.5, 0.), (0.2, 0.1), (5., 5.), (5.1, 5.)];
+        let station_lines = vec![vec![0], vec![0], vec![1], vec![0], vec![1]];
+        let close = close_stations(&pts, &station_lines, 1.);
+        let pairs: Vec<(usize, usize)> = close.iter().map(|(p, _)| *p).collect();
+        assert_eq!(pairs, vec![(0, 2), (1, 2), (3, 4)]);
+    }
+}
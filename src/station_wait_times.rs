@@ -1,8 +1,16 @@
-use super::build_graph::{NetGraph, Network};
+use super::build_graph::{LineFrequency, NetGraph, Network};
+use super::rand_utils;
 use super::MResult;
 use rand::prelude::*;
 use serde::Deserialize;
 use simplegraph::Graph;
+use std::collections::BTreeMap;
+
+const DEFAULT_WAIT_FACTOR: f64 = 0.5;
+
+fn get_default_wait_factor() -> f64 {
+    DEFAULT_WAIT_FACTOR
+}
 
 #[derive(Deserialize)]
 pub struct StationWaitTimeConfig {
@@ -22,8 +30,82 @@ pub fn add_wait_time(mut net: Network, conf: &StationWaitTimeConfig) -> MResult<
     Ok(net)
 }
 
+/// Frequency of each line, in vehicles per unit of time.
+#[derive(Deserialize)]
+pub struct FrequencyConfig {
+    /// Range of the random frequency of the lines not in `lines`.
+    pub range: Option<(f64, f64)>,
+    #[serde(default)]
+    pub lines: BTreeMap<usize, f64>,
+    /// Expected wait over the headway: 0.5 when
+    /// passengers arrive at random on a regular service.
+    #[serde(default = "get_default_wait_factor")]
+    pub wait_factor: f64,
+}
+
+/// Give each line a frequency and set the station weights to the
+/// expected wait for the first vehicle of any line serving them.
+/// The wait for a specific line is kept in `line_frequencies`.
+pub fn add_line_frequencies(mut net: Network, conf: &FrequencyConfig) -> MResult<Network> {
+    let line_count = net.line_ids.iter().max().map_or(0, |l| l + 1);
+    let mut frequencies = Vec::with_capacity(line_count);
+    for line in 0..line_count {
+        let frequency = match (conf.lines.get(&line), conf.range) {
+            (Some(f), _) => *f,
+            (None, Some(range)) => rand_utils::random_in_range(range),
+            (None, None) => return Err(format!("no frequency for line {line}").into()),
+        };
+        if frequency <= 0. {
+            return Err(format!("line {line} has frequency {frequency}").into());
+        }
+        frequencies.push(LineFrequency {
+            frequency,
+            boarding_wait: conf.wait_factor / frequency,
+        });
+    }
+    let station_frequency: Vec<f64> = net
+        .station_lines()
+        .iter()
+        .map(|lines| lines.iter().map(|l| frequencies[*l].frequency).sum())
+        .collect();
+    net.graph
+        .update_all_nodes_weight(|i, _| match station_frequency[i] {
+            f if f > 0. => conf.wait_factor / f,
+            _ => 0.,
+        });
+    net.line_frequencies = frequencies;
+    Ok(net)
+}
+
 fn apply_wait_times(graph: &mut NetGraph, conf: &StationWaitTimeConfig) -> MResult<()> {
     let distr = conf.new_lognormal()?;
     graph.update_all_nodes_weight(|_, _| distr.sample(&mut rand::thread_rng()));
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::build_graph::{line_arcs, test_network};
+
+    #[test]
+    fn test_line_frequencies() {
+        // two lines crossing at station 1
+        let lines = vec![vec![0, 1, 2], vec![3, 1, 4]];
+        let net = test_network(5, lines.clone(), &line_arcs(&lines, 1.));
+        let conf = FrequencyConfig {
+            range: Some((5., 6.)),
+            lines: BTreeMap::from([(0, 2.)]),
+            wait_factor: DEFAULT_WAIT_FACTOR,
+        };
+        let net = add_line_frequencies(net, &conf).unwrap();
+        let f1 = net.line_frequencies[1].frequency;
+        assert!((5. ..6.).contains(&f1));
+        assert_eq!(net.line_frequencies[0].boarding_wait, 0.25);
+        let waits: Vec<f64> = net.graph.node_iterator().map(|(_, w)| w).collect();
+        assert_eq!(waits[0], 0.25);
+        assert_eq!(waits[1], 0.5 / (2. + f1));
+        assert_eq!(waits[3], 0.5 / f1);
+    }
+}
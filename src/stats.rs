@@ -0,0 +1,273 @@
+use crate::build_graph::{self, Network, Pt};
+use crate::shortest_path;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+#[derive(Debug, Serialize)]
+pub struct NetworkStats {
+    pub stations: usize,
+    pub lines: usize,
+    pub interchanges: usize,
+    /// Number of stations with the given count of adjacent stations.
+    pub degree_distribution: Vec<usize>,
+    /// Length of the tracks, each section counted once.
+    pub total_length: f64,
+    /// Length of the first pattern of each line.
+    pub line_lengths: Vec<f64>,
+    pub average_spacing: f64,
+    /// Computed on the arc weights, over the connected pairs of stations.
+    pub diameter: f64,
+    pub average_path_length: f64,
+    pub average_transfers: f64,
+    /// Ratio between the length of each line and the
+    /// distance between its terminals.
+    pub line_circuity: Vec<f64>,
+    /// Independent cycles of the graph, express arcs included.
+    pub cyclomatic_number: usize,
+}
+
+impl NetworkStats {
+    fn scalars(&self) -> [(&'static str, f64); 9] {
+        [
+            ("stations", self.stations as f64),
+            ("lines", self.lines as f64),
+            ("interchanges", self.interchanges as f64),
+            ("total_length", self.total_length),
+            ("average_spacing", self.average_spacing),
+            ("diameter", self.diameter),
+            ("average_path_length", self.average_path_length),
+            ("average_transfers", self.average_transfers),
+            ("cyclomatic_number", self.cyclomatic_number as f64),
+        ]
+    }
+}
+
+pub fn network_stats(net: &Network) -> NetworkStats {
+    let sections = sections(net);
+    let neighbours = neighbours(net);
+    let tracks = track_sections(net);
+    let total_length: f64 = tracks
+        .iter()
+        .map(|(i, j)| distance(net.points[*i], net.points[*j]))
+        .sum();
+    let line_patterns = first_patterns(net);
+    let paths = path_stats(net, &sections);
+    let arcs: usize = neighbours.iter().map(|n| n.len()).sum::<usize>() / 2;
+    let components = build_graph::components(&net.graph)
+        .into_iter()
+        .collect::<BTreeSet<_>>()
+        .len();
+
+    NetworkStats {
+        stations: net.points.len(),
+        lines: line_patterns.len(),
+        interchanges: net.station_lines().iter().filter(|l| l.len() > 1).count(),
+        degree_distribution: degree_distribution(&neighbours),
+        total_length,
+        line_lengths: line_patterns
+            .iter()
+            .map(|p| pattern_length(&net.points, p))
+            .collect(),
+        average_spacing: ratio(total_length, tracks.len()),
+        diameter: paths.diameter,
+        average_path_length: paths.average_length,
+        average_transfers: paths.average_transfers,
+        line_circuity: line_patterns
+            .iter()
+            .map(|p| circuity(&net.points, p))
+            .collect(),
+        cyclomatic_number: (arcs + components).saturating_sub(net.points.len()),
+    }
+}
+
+/// One row for each scalar statistic with its mean,
+/// standard deviation, minimum and maximum over *stats*.
+pub fn aggregate_table(stats: &[NetworkStats]) -> String {
+    let mut output = String::from("statistic,mean,std,min,max\n");
+    if stats.is_empty() {
+        return output;
+    }
+    let count = stats.len() as f64;
+    let rows: Vec<_> = stats.iter().map(|s| s.scalars()).collect();
+    for (k, (name, _)) in rows[0].iter().enumerate() {
+        let values: Vec<f64> = rows.iter().map(|r| r[k].1).collect();
+        let mean = values.iter().sum::<f64>() / count;
+        let var = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / count;
+        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        writeln!(output, "{name},{mean},{},{min},{max}", var.sqrt()).unwrap();
+    }
+    output
+}
+
+/// Map each pair of consecutive stations to the lines running between them.
+fn sections(net: &Network) -> BTreeMap<(usize, usize), BTreeSet<usize>> {
+    let mut output: BTreeMap<_, BTreeSet<_>> = BTreeMap::new();
+    for (pattern, line) in net.lines.iter().zip(&net.line_ids) {
+        for pair in pattern.windows(2).filter(|p| p[0] != p[1]) {
+            let key = (pair[0].min(pair[1]), pair[0].max(pair[1]));
+            output.entry(key).or_default().insert(*line);
+        }
+    }
+    output
+}
+
+fn track_sections(net: &Network) -> BTreeSet<(usize, usize)> {
+    net.tracks
+        .iter()
+        .flat_map(|t| t.windows(2))
+        .filter(|p| p[0] != p[1])
+        .map(|p| (p[0].min(p[1]), p[0].max(p[1])))
+        .collect()
+}
+
+fn neighbours(net: &Network) -> Vec<BTreeSet<usize>> {
+    let mut output = vec![BTreeSet::new(); net.points.len()];
+    for (i, j, _) in net.graph.arc_iterator().filter(|(i, j, _)| i != j) {
+        output[i].insert(j);
+        output[j].insert(i);
+    }
+    output
+}
+
+fn degree_distribution(neighbours: &[BTreeSet<usize>]) -> Vec<usize> {
+    let max = neighbours.iter().map(|n| n.len()).max().unwrap_or(0);
+    let mut output = vec![0; max + 1];
+    for n in neighbours {
+        output[n.len()] += 1;
+    }
+    output
+}
+
+fn first_patterns(net: &Network) -> Vec<&[usize]> {
+    let mut output: BTreeMap<usize, &[usize]> = BTreeMap::new();
+    for (pattern, line) in net.lines.iter().zip(&net.line_ids) {
+        output.entry(*line).or_insert(pattern);
+    }
+    output.into_values().collect()
+}
+
+struct PathStats {
+    diameter: f64,
+    average_length: f64,
+    average_transfers: f64,
+}
+
+fn path_stats(net: &Network, sections: &BTreeMap<(usize, usize), BTreeSet<usize>>) -> PathStats {
+    let mut diameter: f64 = 0.;
+    let mut length = 0.;
+    let mut transfers = 0;
+    let mut pairs = 0;
+    for src in 0..net.points.len() {
+        let paths = shortest_path::dijkstra(&net.graph, src);
+        for dst in (src + 1)..net.points.len() {
+            if let Some(path) = paths.path_to(dst) {
+                diameter = diameter.max(paths.dist[dst]);
+                length += paths.dist[dst];
+                transfers += path_transfers(&path, sections);
+                pairs += 1;
+            }
+        }
+    }
+    PathStats {
+        diameter,
+        average_length: ratio(length, pairs),
+        average_transfers: ratio(transfers as f64, pairs),
+    }
+}
+
+/// Count the line changes needed to follow *path*, staying on
+/// a line as long as possible. Walking between stations ends
+/// the current ride.
+fn path_transfers(path: &[usize], sections: &BTreeMap<(usize, usize), BTreeSet<usize>>) -> usize {
+    let mut boardings = 0;
+    let mut current: Option<BTreeSet<usize>> = None;
+    for pair in path.windows(2) {
+        let key = (pair[0].min(pair[1]), pair[0].max(pair[1]));
+        let lines = sections.get(&key).cloned().unwrap_or_default();
+        current = match current {
+            _ if lines.is_empty() => None,
+            Some(curr) if !curr.is_disjoint(&lines) => {
+                Some(curr.intersection(&lines).copied().collect())
+            }
+            _ => {
+                boardings += 1;
+                Some(lines)
+            }
+        };
+    }
+    boardings.max(1) - 1
+}
+
+fn pattern_length(pts: &[Pt], pattern: &[usize]) -> f64 {
+    pattern
+        .windows(2)
+        .map(|p| distance(pts[p[0]], pts[p[1]]))
+        .sum()
+}
+
+fn circuity(pts: &[Pt], pattern: &[usize]) -> f64 {
+    match (pattern.first(), pattern.last()) {
+        (Some(first), Some(last)) => {
+            pattern_length(pts, pattern) / distance(pts[*first], pts[*last])
+        }
+        _ => 0.,
+    }
+}
+
+fn ratio(num: f64, den: usize) -> f64 {
+    if den > 0 {
+        num / den as f64
+    } else {
+        0.
+    }
+}
+
+fn distance(p1: Pt, p2: Pt) -> f64 {
+    let (x1, y1) = p1;
+    let (x2, y2) = p2;
+    (x1 - x2).hypot(y1 - y2)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::build_graph::{line_arcs, test_network};
+
+    #[test]
+    fn test_path_transfers() {
+        let mut sections = BTreeMap::new();
+        sections.insert((0, 1), BTreeSet::from([0, 1]));
+        sections.insert((1, 2), BTreeSet::from([1]));
+        sections.insert((2, 3), BTreeSet::from([2]));
+        sections.insert((3, 4), BTreeSet::from([0, 2]));
+        assert_eq!(path_transfers(&[0, 1, 2], &sections), 0);
+        assert_eq!(path_transfers(&[0, 1, 2, 3, 4], &sections), 1);
+        assert_eq!(path_transfers(&[0, 1, 5, 3, 4], &sections), 1);
+        assert_eq!(path_transfers(&[0], &sections), 0);
+    }
+
+    #[test]
+    fn test_network_stats() {
+        // a cross: line 0 runs horizontally, line 1 vertically
+        let points = vec![(0., 0.), (1., 0.), (2., 0.), (1., 1.), (1., -1.)];
+        let lines = vec![vec![0, 1, 2], vec![3, 1, 4]];
+        let arcs = line_arcs(&lines, 1.);
+        let mut net = test_network(points.len(), lines, &arcs);
+        net.points = points;
+        let stats = network_stats(&net);
+        assert_eq!(stats.interchanges, 1);
+        assert_eq!(stats.degree_distribution, vec![0, 4, 0, 0, 1]);
+        assert_eq!(stats.total_length, 4.);
+        assert_eq!(stats.line_circuity, vec![1., 1.]);
+        assert_eq!(stats.diameter, 2.);
+        assert_eq!(stats.average_path_length, 1.6);
+        assert_eq!(stats.average_transfers, 0.4);
+        assert_eq!(stats.cyclomatic_number, 0);
+
+        let table = aggregate_table(&[stats]);
+        assert!(table.contains("\ndiameter,2,0,2,2\n"));
+    }
+}
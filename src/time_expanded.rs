@@ -0,0 +1,348 @@
+use crate::build_graph::{NetGraph, Network, TransferKind};
+use crate::shortest_path;
+use crate::MResult;
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+
+#[derive(Deserialize)]
+pub struct TimeExpandedConfig {
+    pub base_name: String,
+    /// Trips leave their first station before this time.
+    pub horizon: f64,
+    /// Time spent by a vehicle at each intermediate station.
+    #[serde(default)]
+    pub dwell: f64,
+    /// Shortest time to change vehicle, walking excluded.
+    #[serde(default)]
+    pub min_change: f64,
+}
+
+/// A vehicle running a service pattern in one direction.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Trip {
+    pub line: usize,
+    pub pattern: usize,
+    pub forward: bool,
+    pub departure: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeKind {
+    Arrival,
+    Departure,
+    /// A passenger waiting at a station, off board.
+    Station,
+}
+
+/// Arrivals and departures belong to a trip, station nodes do not.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Node {
+    pub kind: NodeKind,
+    pub station: usize,
+    pub trip: Option<usize>,
+    pub time: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArcKind {
+    /// On board, between two consecutive stations.
+    Ride,
+    /// On board, stopped at a station.
+    Dwell,
+    /// Off board, from a station node to the next one.
+    Wait,
+    /// From a station node to the departure at the same time.
+    Board,
+    /// From an arrival to the first station node reachable
+    /// after changing, possibly walking to another station.
+    Transfer,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Arc {
+    pub from: usize,
+    pub to: usize,
+    pub kind: ArcKind,
+    pub cost: f64,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct TimeExpandedGraph {
+    pub trips: Vec<Trip>,
+    pub nodes: Vec<Node>,
+    pub arcs: Vec<Arc>,
+}
+
+impl TimeExpandedGraph {
+    fn add_node(&mut self, kind: NodeKind, station: usize, time: f64) -> usize {
+        let trip = match kind {
+            NodeKind::Station => None,
+            _ => Some(self.trips.len() - 1),
+        };
+        self.nodes.push(Node {
+            kind,
+            station,
+            trip,
+            time,
+        });
+        self.nodes.len() - 1
+    }
+
+    fn add_arc(&mut self, from: usize, to: usize, kind: ArcKind, cost: f64) {
+        self.arcs.push(Arc {
+            from,
+            to,
+            kind,
+            cost,
+        });
+    }
+}
+
+/// Expand *net* over time: each pattern runs in both directions
+/// at the frequency of its line, starting at a random offset within
+/// the headway. Arc costs are time differences, so the shortest path
+/// from a station node gives the earliest arrival. Passengers wait
+/// and change on station nodes only, a change always takes at least
+/// `min_change`. Lines without a frequency are left out; fails when
+/// two consecutive stations of a pattern are not connected by an arc.
+pub fn time_expanded_graph(net: &Network, conf: &TimeExpandedConfig) -> MResult<TimeExpandedGraph> {
+    let mut output = TimeExpandedGraph::default();
+    for (p, pattern) in net.lines.iter().enumerate() {
+        let line = net.line_ids[p];
+        let frequency = match net.line_frequencies.get(line) {
+            Some(f) => f.frequency,
+            None => continue,
+        };
+        let headway = 1. / frequency;
+        for forward in [true, false] {
+            let stations: Vec<usize> = if forward {
+                pattern.clone()
+            } else {
+                pattern.iter().rev().copied().collect()
+            };
+            let drives = drive_times(&net.graph, p, &stations)?;
+            let mut departure = fastrand::f64() * headway;
+            while departure < conf.horizon {
+                output.trips.push(Trip {
+                    line,
+                    pattern: p,
+                    forward,
+                    departure,
+                });
+                add_trip(&mut output, &stations, &drives, departure, conf.dwell);
+                departure += headway;
+            }
+        }
+    }
+    add_station_arcs(&mut output, net, conf.min_change);
+    Ok(output)
+}
+
+fn drive_times(g: &NetGraph, pattern: usize, stations: &[usize]) -> MResult<Vec<f64>> {
+    stations
+        .windows(2)
+        .map(|p| {
+            shortest_path::arc_weight(g, p[0], p[1]).ok_or_else(|| {
+                format!("pattern {pattern} has no arc from {} to {}", p[0], p[1]).into()
+            })
+        })
+        .collect()
+}
+
+fn add_trip(
+    output: &mut TimeExpandedGraph,
+    stations: &[usize],
+    drives: &[f64],
+    start: f64,
+    dwell: f64,
+) {
+    let mut prev = output.add_node(NodeKind::Departure, stations[0], start);
+    for (k, (next, drive)) in stations[1..].iter().zip(drives).enumerate() {
+        let time = output.nodes[prev].time + drive;
+        let arr = output.add_node(NodeKind::Arrival, *next, time);
+        output.add_arc(prev, arr, ArcKind::Ride, *drive);
+        if k + 1 < drives.len() {
+            prev = output.add_node(NodeKind::Departure, *next, time + dwell);
+            output.add_arc(arr, prev, ArcKind::Dwell, dwell);
+        }
+    }
+}
+
+/// Give each departure a station node to board from, chain the
+/// station nodes of each station by time, then link every arrival
+/// to the first station node reachable from it.
+fn add_station_arcs(output: &mut TimeExpandedGraph, net: &Network, min_change: f64) {
+    let mut waiting: Vec<Vec<usize>> = vec![vec![]; net.points.len()];
+    for dep in 0..output.nodes.len() {
+        let Node {
+            kind,
+            station,
+            time,
+            ..
+        } = output.nodes[dep];
+        if kind == NodeKind::Departure {
+            let node = output.add_node(NodeKind::Station, station, time);
+            output.add_arc(node, dep, ArcKind::Board, 0.);
+            waiting[station].push(node);
+        }
+    }
+    for nodes in &mut waiting {
+        nodes.sort_by(|a, b| output.nodes[*a].time.total_cmp(&output.nodes[*b].time));
+        for pair in nodes.windows(2) {
+            let cost = output.nodes[pair[1]].time - output.nodes[pair[0]].time;
+            output.add_arc(pair[0], pair[1], ArcKind::Wait, cost);
+        }
+    }
+
+    let mut walks: Vec<Vec<(usize, f64)>> = (0..net.points.len()).map(|s| vec![(s, 0.)]).collect();
+    for t in net
+        .transfers
+        .iter()
+        .filter(|t| t.kind == TransferKind::Walking)
+    {
+        let (i, j) = t.stations;
+        walks[i].push((j, t.distance));
+        walks[j].push((i, t.distance));
+    }
+    for arr in 0..output.nodes.len() {
+        let Node {
+            kind,
+            station,
+            time,
+            ..
+        } = output.nodes[arr];
+        if kind != NodeKind::Arrival {
+            continue;
+        }
+        for (next, walk) in &walks[station] {
+            let ready = time + min_change + walk;
+            let nodes = &waiting[*next];
+            let first = nodes.partition_point(|n| output.nodes[*n].time < ready);
+            if let Some(node) = nodes.get(first) {
+                let cost = output.nodes[*node].time - time;
+                output.add_arc(arr, *node, ArcKind::Transfer, cost);
+            }
+        }
+    }
+}
+
+/// Write one arc per line as `from to cost`.
+pub fn write_edge_list<W: Write>(graph: &TimeExpandedGraph, mut output: W) -> io::Result<()> {
+    for arc in &graph.arcs {
+        writeln!(output, "{} {} {}", arc.from, arc.to, arc.cost)?;
+    }
+    output.flush()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::build_graph::{test_network, LineFrequency};
+    use simplegraph::Graph;
+
+    #[test]
+    fn test_time_expanded_graph() {
+        // line 0: 0 - 1 - 2 every 5, line 1: 3 - 1 every 10
+        let lines = vec![vec![0, 1, 2], vec![3, 1]];
+        let mut net = test_network(4, lines, &[(0, 1, 2.), (1, 2, 3.), (3, 1, 1.)]);
+        let frequency = |f| LineFrequency {
+            frequency: f,
+            boarding_wait: 0.5 / f,
+        };
+        net.line_frequencies = vec![frequency(0.2), frequency(0.1)];
+        let conf = TimeExpandedConfig {
+            base_name: String::new(),
+            horizon: 10.,
+            dwell: 0.5,
+            min_change: 1.,
+        };
+        let te = time_expanded_graph(&net, &conf).unwrap();
+        // two trips each way on line 0, one on line 1
+        assert_eq!(te.trips.len(), 6);
+        // plus a station node for each departure
+        assert_eq!(te.nodes.len(), 4 * 4 + 2 * 2 + 4 * 2 + 2);
+        let count = |kind| te.arcs.iter().filter(|a| a.kind == kind).count();
+        assert_eq!(count(ArcKind::Ride), 4 * 2 + 2);
+        assert_eq!(count(ArcKind::Dwell), 4);
+        assert_eq!(count(ArcKind::Board), 4 * 2 + 2);
+
+        let costs: Vec<f64> = te.arcs[..3].iter().map(|a| a.cost).collect();
+        assert_eq!(costs, vec![2., 0.5, 3.]);
+        for arc in &te.arcs {
+            assert!(arc.cost >= 0.);
+            if arc.kind == ArcKind::Transfer {
+                assert!(arc.cost >= conf.min_change - 1e-9);
+            }
+        }
+
+        let mut edges = vec![];
+        write_edge_list(&te, &mut edges).unwrap();
+        let edges = String::from_utf8(edges).unwrap();
+        assert_eq!(edges.lines().count(), te.arcs.len());
+        assert_eq!(edges.lines().next(), Some("0 1 2"));
+    }
+
+    #[test]
+    fn test_change_takes_min_change() {
+        // line 0: 0 - 1 - 2, line 1: 1 - 3; the first trip of line 1
+        // leaves station 1 right after line 0 stops there, too early
+        // to change: staying on board must not allow to catch it
+        let lines = vec![vec![0, 1, 2], vec![1, 3]];
+        let net = test_network(4, lines, &[(0, 1, 2.), (1, 2, 1.), (1, 3, 1.)]);
+        let mut te = TimeExpandedGraph::default();
+        for (pattern, stations, start) in [
+            (0, vec![0, 1, 2], 0.),
+            (1, vec![1, 3], 2.6),
+            (1, vec![1, 3], 12.6),
+        ] {
+            te.trips.push(Trip {
+                line: pattern,
+                pattern,
+                forward: true,
+                departure: start,
+            });
+            let drives = drive_times(&net.graph, pattern, &stations).unwrap();
+            add_trip(&mut te, &stations, &drives, start, 0.5);
+        }
+        add_station_arcs(&mut te, &net, 1.);
+
+        let mut graph = NetGraph::new_direct(te.nodes.len());
+        for arc in &te.arcs {
+            graph.add_new_arc(arc.from, arc.to, arc.cost);
+        }
+        let start = te
+            .nodes
+            .iter()
+            .position(|n| n.kind == NodeKind::Station && n.station == 0)
+            .unwrap();
+        let dist = shortest_path::dijkstra(&graph, start).dist;
+        // costs are time differences and the start node is at time 0
+        let earliest = te
+            .nodes
+            .iter()
+            .zip(&dist)
+            .filter(|(n, d)| n.station == 3 && d.is_finite())
+            .map(|(n, _)| n.time)
+            .min_by(f64::total_cmp);
+        assert_eq!(earliest, Some(13.6));
+    }
+
+    #[test]
+    fn test_missing_arc() {
+        let mut net = test_network(3, vec![vec![0, 1, 2]], &[(0, 1, 1.)]);
+        net.line_frequencies = vec![LineFrequency {
+            frequency: 1.,
+            boarding_wait: 0.5,
+        }];
+        let conf = TimeExpandedConfig {
+            base_name: String::new(),
+            horizon: 1.,
+            dwell: 0.,
+            min_change: 0.,
+        };
+        assert!(time_expanded_graph(&net, &conf).is_err());
+    }
+}
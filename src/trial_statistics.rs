@@ -0,0 +1,103 @@
+use crate::bezier_point_factory::SamplingStatistics;
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TrialFailure {
+    Sampling,
+    SelfIntersection,
+    NoIntersections,
+    ShallowCrossing,
+    Disconnected,
+    /// A branch track does not end on a station of its trunk.
+    BranchJunction,
+    StationClearance,
+    NotRobust,
+    Targets,
+}
+
+impl fmt::Display for TrialFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Sampling => "terminal sampling",
+            Self::SelfIntersection => "self intersecting line",
+            Self::NoIntersections => "line without intersections",
+            Self::ShallowCrossing => "shallow crossing",
+            Self::Disconnected => "disconnected network",
+            Self::BranchJunction => "branch off its trunk",
+            Self::StationClearance => "stations too close",
+            Self::NotRobust => "bridge or articulation station",
+            Self::Targets => "targets not met",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl std::error::Error for TrialFailure {}
+
+#[derive(Debug, Default)]
+pub struct TrialStatistics {
+    pub trials: usize,
+    /// Lines resampled to fix a trial instead of restarting it.
+    pub repairs: usize,
+    pub sampling: SamplingStatistics,
+    failures: BTreeMap<TrialFailure, usize>,
+}
+
+impl TrialStatistics {
+    pub fn new_trial(&mut self) {
+        self.trials += 1;
+    }
+
+    pub fn add_failure(&mut self, failure: TrialFailure) {
+        *self.failures.entry(failure).or_default() += 1;
+    }
+}
+
+impl fmt::Display for TrialStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "trials: {}, terminal points accepted: {}, rejected: {}",
+            self.trials, self.sampling.accepted, self.sampling.rejected
+        )?;
+        if self.sampling.poisson_rejected > 0 {
+            let rejected = self.sampling.poisson_rejected;
+            write!(f, ", poisson-disk candidates rejected: {rejected}")?;
+        }
+        if self.repairs > 0 {
+            write!(f, ", repaired lines: {}", self.repairs)?;
+        }
+        for (failure, count) in &self.failures {
+            write!(f, ", {failure}: {count}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_failure_report() {
+        let mut stats = TrialStatistics::default();
+        stats.add_failure(TrialFailure::Disconnected);
+        stats.add_failure(TrialFailure::Sampling);
+        stats.add_failure(TrialFailure::Disconnected);
+
+        assert_eq!(
+            stats.to_string(),
+            "trials: 0, terminal points accepted: 0, rejected: 0, terminal sampling: 1, disconnected network: 2"
+        );
+        stats.repairs = 3;
+        assert!(stats
+            .to_string()
+            .contains("rejected: 0, repaired lines: 3, terminal"));
+        stats.sampling.poisson_rejected = 12;
+        assert!(stats
+            .to_string()
+            .contains("rejected: 0, poisson-disk candidates rejected: 12, repaired"));
+    }
+}